@@ -0,0 +1,73 @@
+// Runs broadcast nodes as a local cluster over TCP, and talks to one as a client.
+//
+//   cluster start [--nodes N] [--port P] [--bin PATH] [--verbose]
+//   cluster broadcast VALUE [--node ID] [--port P]
+//   cluster read [--node ID] [--port P]
+
+use std::env;
+use std::io;
+use std::net::SocketAddr;
+
+use maelstrom_broadcast::cluster::{self, Cluster, ClusterConfig};
+use maelstrom_broadcast::maelstrom_node::init_logging;
+use serde_json::{json, Value};
+
+const USAGE: &str = "usage: cluster start [--nodes N] [--port P] [--bin PATH] [--verbose]
+       cluster broadcast VALUE [--node ID] [--port P]
+       cluster read [--node ID] [--port P]";
+
+#[tokio::main]
+async fn main() -> io::Result<()> {
+    init_logging();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let flag = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).cloned();
+
+    let port: u16 = parse(flag("--port").unwrap_or_else(|| "7000".to_string()))?;
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let node = flag("--node").unwrap_or_else(|| "n0".to_string());
+
+    match args.first().map(String::as_str) {
+        Some("start") => {
+            // Nodes live next to this binary unless told otherwise
+            let bin = match flag("--bin") {
+                Some(bin) => bin.into(),
+                None => env::current_exe()?.with_file_name("maelstrom-broadcast"),
+            };
+            let config = ClusterConfig {
+                nodes: parse(flag("--nodes").unwrap_or_else(|| "5".to_string()))?,
+                client_addr: addr,
+                bin,
+                verbose: args.iter().any(|a| a == "--verbose"),
+            };
+
+            let cluster = Cluster::start(config).await?;
+            println!("{} nodes listening for clients on {}", cluster.node_ids().len(), cluster.client_addr());
+
+            // Run until interrupted; dropping the cluster takes the nodes down with it
+            tokio::signal::ctrl_c().await?;
+            Ok(())
+        },
+        Some("broadcast") => {
+            let value = args.get(1).ok_or_else(|| invalid(USAGE))?;
+            // Anything that isn't valid JSON is sent as a string
+            let value: Value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.clone()));
+            let reply = cluster::call(addr, &node, json!({"type": "broadcast", "message": value})).await?;
+            println!("{}", reply);
+            Ok(())
+        },
+        Some("read") => {
+            let reply = cluster::call(addr, &node, json!({"type": "read"})).await?;
+            println!("{}", reply);
+            Ok(())
+        },
+        _ => Err(invalid(USAGE)),
+    }
+}
+
+fn parse<T: std::str::FromStr>(s: String) -> io::Result<T> {
+    s.parse().map_err(|_| invalid(&format!("invalid value: {}", s)))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg.to_string())
+}
@@ -0,0 +1,644 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::maelstrom_node::{ErrorCode, Handler, Message, RpcError, Runtime, Timer, Traffic};
+use crate::message_set::{MessageList, MessageSet};
+use crate::store::Store;
+
+// Every kind of message the broadcast workload sends or receives, tagged by its "type" field
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Payload {
+    // Any JSON value can be broadcast; values are told apart by their canonical encoding
+    Broadcast {
+        message: Value,
+    },
+    BroadcastOk,
+    // A plain Maelstrom read, or with `since` only the values added after that version
+    Read {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        since: Option<u64>,
+    },
+    ReadOk {
+        messages: MessageList,
+
+        // Where the next incremental read should pick up, only sent back to incremental reads
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<u64>,
+    },
+    Topology {
+        topology: HashMap<String, Vec<String>>,
+    },
+    TopologyOk,
+    Error {
+        code: ErrorCode,
+        text: String,
+    },
+
+    // A batch of values pushed to a neighbor
+    Gossip {
+        messages: Vec<Value>,
+    },
+    GossipOk,
+
+    // An anti-entropy summary of the sender's values in some regions of the hash space, or in
+    // all of it if none are given, one bucket per non-empty region a level down
+    Sync {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        within: Vec<Region>,
+        digest: Vec<Bucket>,
+    },
+    // The regions that differed. Small ones come with the hash of every value the replier
+    // holds in them, and bigger ones are left for the sender to summarize a level further down.
+    SyncOk {
+        buckets: Vec<Region>,
+        hashes: Vec<u64>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        deeper: Vec<Region>,
+    },
+    // Hashes from a sync_ok that the sender doesn't hold, asking for their values as gossip
+    SyncPull {
+        hashes: Vec<u64>,
+    },
+
+    // Our counters, for comparing gossip strategies
+    Stats,
+    StatsOk {
+        stats: Stats,
+    },
+
+    // Anything with a type we don't recognize
+    #[serde(other)]
+    Unknown,
+}
+
+// Anti-entropy splits the hash space into regions by the lowest bits of each hash, this many
+// more at each level down, so every region has 64 below it
+const SYNC_BITS: u32 = 6;
+const SYNC_BUCKETS: usize = 1 << SYNC_BITS;
+
+// As far down as regions go before they run out of bits
+const SYNC_MAX_DEPTH: u32 = u64::BITS / SYNC_BITS;
+
+// A differing region where the replier holds at most this many values is settled by listing
+// their hashes. Anything bigger is split again, so a sync round costs about as much as the
+// difference it finds rather than as much as the set.
+const SYNC_LEAF: u64 = 16;
+
+// Every hash whose lowest SYNC_BITS * depth bits are prefix. Depth 0 is the whole space.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+pub struct Region {
+    depth: u32,
+    prefix: u64,
+}
+
+impl Region {
+    // The region at a depth that a hash falls into
+    fn of(hash: u64, depth: u32) -> Region {
+        let mask = match depth * SYNC_BITS {
+            bits if bits >= u64::BITS => u64::MAX,
+            bits => (1 << bits) - 1,
+        };
+        Region { depth, prefix: hash & mask }
+    }
+
+    // The regions one level down that make this one up
+    fn children(self) -> impl Iterator<Item = Region> {
+        let shift = self.depth * SYNC_BITS;
+        (0..SYNC_BUCKETS as u64).map(move |i| Region {
+            depth: self.depth + 1,
+            prefix: self.prefix | i << shift,
+        })
+    }
+}
+
+// One non-empty region of an anti-entropy digest
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct Bucket {
+    #[serde(flatten)]
+    region: Region,
+    count: u64,
+    hash: u64,
+}
+
+// Looks up which of a set of regions, if any, a hash falls into
+struct Regions {
+    depths: Vec<u32>,
+    set: HashSet<Region>,
+}
+
+impl Regions {
+    fn new(regions: &[Region]) -> Regions {
+        let mut depths: Vec<u32> = regions.iter().map(|r| r.depth).collect();
+        depths.sort();
+        depths.dedup();
+        Regions {
+            depths,
+            set: regions.iter().copied().collect(),
+        }
+    }
+
+    fn find(&self, hash: u64) -> Option<Region> {
+        self.depths.iter().map(|d| Region::of(hash, *d)).find(|r| self.set.contains(r))
+    }
+}
+
+// Counters for what a node has done since it started
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Stats {
+    pub traffic: Traffic,
+
+    // Values we were told about but already had
+    pub duplicates: u64,
+
+    // Values carried by gossip messages, retransmissions included; divide by the gossip
+    // count in traffic for the average batch size
+    pub gossip_values: u64,
+
+    // Gossip retransmissions, and deliveries abandoned after too many attempts
+    pub retries: u64,
+    pub abandoned: u64,
+
+    // Deliveries a neighbor confirmed, and how long that took from the first attempt. A
+    // value is everywhere once every hop on its way has been confirmed, so this is how
+    // far each hop adds to convergence.
+    pub acked: u64,
+    pub ack_lag_ms_total: u64,
+    pub ack_lag_ms_max: u64,
+}
+
+// A batch of gossip that a peer has not acknowledged yet
+#[derive(Debug)]
+struct Delivery {
+    // The values being delivered
+    values: Vec<Value>,
+
+    // msg_ids of earlier attempts, since an ack for any of them settles the delivery
+    earlier: Vec<u64>,
+    attempts: u32,
+    deadline: Instant,
+
+    // When the first attempt went out
+    since: Instant,
+}
+
+// The broadcast workload: everything we've seen, and what's still on its way to our neighbors
+#[derive(Debug)]
+pub struct Broadcast {
+    config: Config,
+    neighbors: Vec<String>,
+
+    // Every value we hold
+    messages: MessageSet,
+
+    // Our top-level anti-entropy digest, one bucket per region, kept up to date as values
+    // arrive. Digests further down are worked out when a peer asks for them.
+    buckets: Vec<Bucket>,
+
+    // Values we've seen but not yet gossiped, per neighbor. Ordered maps keep what we send,
+    // and in what order, reproducible.
+    outbox: BTreeMap<String, Vec<Value>>,
+
+    // Gossip still waiting on a gossip_ok, per peer and keyed by the msg_id it was sent with
+    inflight: BTreeMap<String, BTreeMap<u64, Delivery>>,
+
+    // Round-robin position in our neighbor list for anti-entropy
+    sync_cursor: usize,
+
+    // Our on-disk copy, once init has told us who we are, if we have a data directory
+    store: Option<Store>,
+
+    stats: Stats,
+}
+
+impl Broadcast {
+    // Shortcut for defining a new broadcast node
+    pub fn new(config: Config) -> Broadcast {
+        let storage = config.storage;
+        Broadcast {
+            config,
+            neighbors: Vec::new(),
+            messages: MessageSet::new(storage),
+            buckets: Region::default()
+                .children()
+                .map(|region| Bucket { region, ..Default::default() })
+                .collect(),
+            outbox: BTreeMap::new(),
+            inflight: BTreeMap::new(),
+            sync_cursor: 0,
+            store: None,
+            stats: Stats::default(),
+        }
+    }
+
+    // Spreads a value of our own to every node, as if a client had broadcast it to us
+    pub fn publish(&mut self, rt: &Runtime, value: Value) -> Result<(), RpcError> {
+        let new = self.record([value])?;
+        self.broadcast(rt, rt.id(), &new);
+        Ok(())
+    }
+
+    // How many values we hold, which is also the version incremental reads pick up from
+    pub fn version(&self) -> usize {
+        self.messages.len()
+    }
+
+    // Every value first seen after the given version, in the order we saw them
+    pub fn values_since(&self, version: usize) -> MessageList {
+        self.messages.since(version)
+    }
+
+    // Stores values, returning the ones that were new to us. With a data directory, new values
+    // are on disk before this returns, and so before anyone hears that we have them. A batch
+    // with any value our storage can't hold is refused as a whole.
+    fn record(&mut self, values: impl IntoIterator<Item = Value>) -> Result<Vec<Value>, RpcError> {
+        let values: Vec<Value> = values.into_iter().collect();
+        for v in &values {
+            self.messages.check(v)?;
+        }
+
+        let mut new = Vec::new();
+        for v in values {
+            match self.messages.add(v.clone()) {
+                Some(hash) => {
+                    self.add_to_digest(hash);
+                    new.push(v);
+                },
+                None => self.stats.duplicates += 1,
+            }
+        }
+
+        if let Some(store) = &mut self.store {
+            store
+                .append(&new)
+                .map_err(|e| RpcError::new(ErrorCode::Crash, format!("could not persist messages: {}", e)))?;
+        }
+        Ok(new)
+    }
+
+    // Folds the on-disk log into a fresh snapshot, if anything has been logged since the last
+    fn compact(&mut self, _rt: &Runtime) -> Result<(), RpcError> {
+        if let Some(store) = self.store.as_mut().filter(|s| s.is_dirty()) {
+            store
+                .compact(&self.messages.all())
+                .map_err(|e| RpcError::new(ErrorCode::Crash, format!("could not compact store: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    // Queues newly seen values for every neighbor; they go out on the next gossip flush
+    fn broadcast(&mut self, rt: &Runtime, src: &str, values: &[Value]) {
+        if values.is_empty() {
+            return;
+        }
+
+        for n in &self.neighbors {
+            // Never send to ourselves or the node that just sent the values to us
+            if n == rt.id() || n == src {
+                continue;
+            }
+
+            self.outbox.entry(n.clone()).or_default().extend_from_slice(values);
+        }
+    }
+
+    // Sends everything queued for each neighbor as a single gossip message
+    fn flush_gossip(&mut self, rt: &Runtime) -> Result<(), RpcError> {
+        let outbox = std::mem::take(&mut self.outbox);
+        for (dest, values) in outbox {
+            if values.is_empty() {
+                continue;
+            }
+
+            self.deliver(rt, dest, values, Vec::new(), 0, Instant::now())?;
+        }
+        Ok(())
+    }
+
+    // Sends a gossip batch and remembers it until the peer acknowledges it
+    fn deliver(
+        &mut self,
+        rt: &Runtime,
+        dest: String,
+        values: Vec<Value>,
+        earlier: Vec<u64>,
+        attempts: u32,
+        since: Instant,
+    ) -> Result<(), RpcError> {
+        let msg_id = rt.send(dest.clone(), Payload::Gossip { messages: values.clone() })?;
+        self.stats.gossip_values += values.len() as u64;
+
+        let delivery = Delivery {
+            values,
+            earlier,
+            attempts: attempts + 1,
+            deadline: Instant::now() + self.backoff(attempts),
+            since,
+        };
+        self.inflight.entry(dest).or_default().insert(msg_id, delivery);
+        Ok(())
+    }
+
+    // Drops a delivery once the peer has confirmed it
+    fn ack(&mut self, src: &str, in_reply_to: u64) {
+        let Some(deliveries) = self.inflight.get_mut(src) else {
+            return;
+        };
+        let id = match deliveries.contains_key(&in_reply_to) {
+            true => Some(in_reply_to),
+            false => deliveries.iter().find(|(_, d)| d.earlier.contains(&in_reply_to)).map(|(id, _)| *id),
+        };
+
+        if let Some(delivery) = id.and_then(|id| deliveries.remove(&id)) {
+            let lag = delivery.since.elapsed().as_millis() as u64;
+            self.stats.acked += 1;
+            self.stats.ack_lag_ms_total += lag;
+            self.stats.ack_lag_ms_max = self.stats.ack_lag_ms_max.max(lag);
+        }
+    }
+
+    // Retransmits every delivery whose deadline has passed, giving up after too many attempts
+    fn retry_expired(&mut self, rt: &Runtime) -> Result<(), RpcError> {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        for (dest, deliveries) in self.inflight.iter_mut() {
+            let ids: Vec<u64> = deliveries
+                .iter()
+                .filter(|(_, d)| d.deadline <= now)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in ids {
+                if let Some(delivery) = deliveries.remove(&id) {
+                    expired.push((dest.clone(), id, delivery));
+                }
+            }
+        }
+
+        for (dest, id, mut delivery) in expired {
+            let max = self.config.retry_max_attempts;
+            if max > 0 && delivery.attempts >= max {
+                warn!(%dest, attempts = delivery.attempts, "giving up on {} values", delivery.values.len());
+                self.stats.abandoned += 1;
+                continue;
+            }
+
+            delivery.earlier.push(id);
+            self.stats.retries += 1;
+            self.deliver(rt, dest, delivery.values, delivery.earlier, delivery.attempts, delivery.since)?;
+        }
+        Ok(())
+    }
+
+    // Our counters, along with the runtime's traffic counts
+    fn stats(&self, rt: &Runtime) -> Stats {
+        Stats {
+            traffic: rt.traffic(),
+            ..self.stats.clone()
+        }
+    }
+
+    // Writes our counters to the log
+    fn report_stats(&mut self, rt: &Runtime) -> Result<(), RpcError> {
+        info!(stats = %serde_json::to_string(&self.stats(rt))?, "stats");
+        Ok(())
+    }
+
+    // Starts an anti-entropy round by sending the next neighbor a summary of what we hold
+    fn anti_entropy(&mut self, rt: &Runtime) -> Result<(), RpcError> {
+        let peers: Vec<&String> = self.neighbors.iter().filter(|n| *n != rt.id()).collect();
+        if peers.is_empty() {
+            return Ok(());
+        }
+
+        let dest = peers[self.sync_cursor % peers.len()].clone();
+        self.sync_cursor = self.sync_cursor.wrapping_add(1);
+
+        let digest = self.digest(&[]);
+        rt.send(dest, Payload::Sync { within: Vec::new(), digest })?;
+        Ok(())
+    }
+
+    // Folds the hash of a value we've just taken in into our digest
+    fn add_to_digest(&mut self, h: u64) {
+        let bucket = &mut self.buckets[Region::of(h, 1).prefix as usize];
+        bucket.count += 1;
+        bucket.hash ^= h;
+    }
+
+    // Summarizes what we hold in the given regions, or everywhere if none are given, as a
+    // count and xor-hash per non-empty region a level down
+    fn digest(&self, within: &[Region]) -> Vec<Bucket> {
+        if within.is_empty() {
+            return self.buckets.iter().filter(|b| b.count > 0).copied().collect();
+        }
+
+        let regions = Regions::new(within);
+        let mut buckets: HashMap<Region, Bucket> = HashMap::new();
+        for h in self.messages.hashes() {
+            if let Some(r) = regions.find(h) {
+                let region = Region::of(h, r.depth + 1);
+                let bucket = buckets.entry(region).or_insert(Bucket { region, ..Default::default() });
+                bucket.count += 1;
+                bucket.hash ^= h;
+            }
+        }
+        buckets.into_values().collect()
+    }
+
+    // Compares a peer's digest with ours, region by region, returning the differing regions
+    // small enough to list and those to split again
+    fn diff(&self, within: &[Region], theirs: &[Bucket]) -> (Vec<Region>, Vec<Region>) {
+        let ours: HashMap<Region, Bucket> = self.digest(within).into_iter().map(|b| (b.region, b)).collect();
+        let theirs: HashMap<Region, Bucket> = theirs.iter().map(|b| (b.region, *b)).collect();
+        let parents = match within.is_empty() {
+            true => vec![Region::default()],
+            false => within.to_vec(),
+        };
+
+        let mut listed = Vec::new();
+        let mut deeper = Vec::new();
+        for region in parents.into_iter().filter(|r| r.depth < SYNC_MAX_DEPTH).flat_map(Region::children) {
+            let mine = ours.get(&region);
+            if mine == theirs.get(&region) {
+                continue;
+            }
+            match mine {
+                Some(b) if b.count > SYNC_LEAF && region.depth < SYNC_MAX_DEPTH => deeper.push(region),
+                _ => listed.push(region),
+            }
+        }
+        (listed, deeper)
+    }
+
+    // The hash of every value we hold that falls into one of the given regions
+    fn hashes_in(&self, regions: &[Region]) -> Vec<u64> {
+        let regions = Regions::new(regions);
+        self.messages.hashes().filter(|h| regions.find(*h).is_some()).collect()
+    }
+
+    // How long to wait on a delivery that has already been attempted this many times
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.min(16));
+        self.config.retry_timeout.saturating_mul(factor).min(self.config.retry_max_backoff)
+    }
+}
+
+impl Handler for Broadcast {
+    type Payload = Payload;
+
+    // Picks neighbors a topology message can later replace, and recovers whatever we had on
+    // disk before telling Maelstrom we're ready
+    fn init(&mut self, rt: &Runtime) -> Result<(), RpcError> {
+        self.neighbors = self.config.topology.default_neighbors(rt.id(), rt.node_ids());
+
+        let Some(dir) = self.config.data_dir.clone() else {
+            return Ok(());
+        };
+
+        let (store, values) = Store::open(&dir, rt.id())
+            .map_err(|e| RpcError::new(ErrorCode::Crash, format!("could not open store in {}: {}", dir.display(), e)))?;
+        // Anything on disk that this storage can't hold was written under another one
+        for v in values {
+            if self.messages.check(&v).is_err() {
+                warn!("dropping stored message {} that {:?} storage can't hold", v, self.config.storage);
+                continue;
+            }
+            if let Some(hash) = self.messages.add(v) {
+                self.add_to_digest(hash);
+            }
+        }
+        info!("recovered {} messages from {}", self.messages.len(), dir.display());
+
+        self.store = Some(store);
+        Ok(())
+    }
+
+    // Works out the reply to a message, if it needs one
+    fn handle(&mut self, rt: &Runtime, msg: &Message<Payload>) -> Result<Option<Payload>, RpcError> {
+        // Look at the message type and decide what to do
+        let reply = match &msg.body.payload {
+            Payload::Broadcast { message } => {
+                // Store the message, and if we haven't seen it before, queue it for our neighbors
+                let new = self.record([message.clone()])?;
+                self.broadcast(rt, &msg.src, &new);
+
+                Payload::BroadcastOk
+            },
+            Payload::Gossip { messages } => {
+                // Keep whatever is new to us and pass only that along
+                let new = self.record(messages.iter().cloned())?;
+                self.broadcast(rt, &msg.src, &new);
+
+                Payload::GossipOk
+            },
+            Payload::GossipOk => {
+                // The peer has our values, so stop retrying them
+                if let Some(in_reply_to) = msg.body.in_reply_to {
+                    self.ack(&msg.src, in_reply_to);
+                }
+                return Ok(None);
+            },
+            Payload::Sync { within, digest } => {
+                // Tell the peer which regions differ, and which values we hold in the small
+                // ones, but leave the values themselves for once we know which ones they lack
+                let (buckets, deeper) = self.diff(within, digest);
+                let hashes = self.hashes_in(&buckets);
+
+                Payload::SyncOk { buckets, hashes, deeper }
+            },
+            Payload::SyncOk { buckets, hashes, deeper } => {
+                // Narrow down the regions that were too big to list
+                if !deeper.is_empty() {
+                    let digest = self.digest(deeper);
+                    rt.send(msg.src.clone(), Payload::Sync { within: deeper.clone(), digest })?;
+                }
+
+                // Push what the peer is missing from the listed regions, and ask for what we are
+                let theirs: HashSet<u64> = hashes.iter().copied().collect();
+                let held: HashSet<u64> = self.hashes_in(buckets).into_iter().collect();
+
+                let missing = self
+                    .messages
+                    .matching(|h| held.contains(&h) && !theirs.contains(&h));
+                if !missing.is_empty() {
+                    self.deliver(rt, msg.src.clone(), missing, Vec::new(), 0, Instant::now())?;
+                }
+                let wanted: Vec<u64> = hashes.iter().filter(|h| !held.contains(h)).copied().collect();
+                if !wanted.is_empty() {
+                    rt.send(msg.src.clone(), Payload::SyncPull { hashes: wanted })?;
+                }
+                return Ok(None);
+            },
+            Payload::SyncPull { hashes } => {
+                // Send the requested values as ordinary gossip, so they are retried until acked
+                let wanted: HashSet<u64> = hashes.iter().copied().collect();
+                let values = self.messages.matching(|h| wanted.contains(&h));
+                if !values.is_empty() {
+                    self.deliver(rt, msg.src.clone(), values, Vec::new(), 0, Instant::now())?;
+                }
+                return Ok(None);
+            },
+            Payload::Read { since: None } => {
+                // Attach all the messages we've seen
+                Payload::ReadOk {
+                    messages: self.messages.all(),
+                    version: None,
+                }
+            },
+            Payload::Read { since: Some(since) } => {
+                // Only what's been added since the client's last read, plus a cursor for the next one
+                Payload::ReadOk {
+                    messages: self.messages.since(*since as usize),
+                    version: Some(self.messages.len() as u64),
+                }
+            },
+            Payload::Topology { topology } => {
+                // Work out our set of neighbors, from Maelstrom's suggestion or our own layout, and store it for later
+                self.neighbors = self.config.topology.suggested_neighbors(rt.id(), rt.node_ids(), topology)?;
+
+                Payload::TopologyOk
+            },
+            Payload::Error { code, text } => {
+                // Nothing we send expects an answer yet, so errors from peers are only worth logging
+                warn!(code = u32::from(*code), "peer returned an error: {}", text);
+                return Ok(None);
+            },
+            Payload::Stats => Payload::StatsOk { stats: self.stats(rt) },
+            Payload::BroadcastOk
+            | Payload::ReadOk { .. }
+            | Payload::TopologyOk
+            | Payload::StatsOk { .. }
+            | Payload::Unknown => {
+                return Err(RpcError::new(ErrorCode::NotSupported, "unsupported message type"));
+            },
+        };
+
+        Ok(Some(reply))
+    }
+
+    // Flush buffered gossip, chase unacknowledged deliveries and reconcile with neighbors
+    // in between requests, so client requests never wait on any of them
+    fn timers(&self) -> Vec<Timer<Broadcast>> {
+        let mut timers = vec![
+            Timer::new(self.config.gossip_interval, Broadcast::flush_gossip),
+            Timer::new(self.config.retry_timeout / 2, Broadcast::retry_expired),
+        ];
+        if !self.config.anti_entropy_interval.is_zero() {
+            timers.push(Timer::new(self.config.anti_entropy_interval, Broadcast::anti_entropy));
+        }
+        if self.config.data_dir.is_some() {
+            timers.push(Timer::new(self.config.snapshot_interval, Broadcast::compact));
+        }
+        if !self.config.stats_interval.is_zero() {
+            timers.push(Timer::new(self.config.stats_interval, Broadcast::report_stats));
+        }
+        timers
+    }
+}
+
@@ -0,0 +1,228 @@
+// A local stand-in for Maelstrom: spawns real node processes that connect back over localhost
+// TCP, runs the init and topology handshake with them, then routes every message by its dest,
+// between nodes and to whichever clients connect.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::maelstrom_node::{Inbound, LineTransport, Message, Outbound, Transport};
+
+// The client the launcher itself uses for the handshake
+const LAUNCHER: &str = "c0";
+
+// Tells apart the calls this process makes as a client
+static NEXT_CALL: AtomicU64 = AtomicU64::new(1);
+
+// How long to wait on each node during startup
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Where each known node or client can be reached
+type Routes = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<String>>>>;
+
+#[derive(Clone, Debug)]
+pub struct ClusterConfig {
+    // How many nodes to spawn
+    pub nodes: usize,
+
+    // Where clients connect
+    pub client_addr: SocketAddr,
+
+    // The node binary to spawn
+    pub bin: PathBuf,
+
+    // Pass node stderr through instead of discarding it
+    pub verbose: bool,
+}
+
+// A running cluster. Dropping it kills the node processes.
+pub struct Cluster {
+    client_addr: SocketAddr,
+    node_ids: Vec<String>,
+    _children: Vec<Child>,
+}
+
+impl Cluster {
+    pub async fn start(config: ClusterConfig) -> io::Result<Cluster> {
+        let routes: Routes = Arc::new(Mutex::new(HashMap::new()));
+
+        // Nodes connect to a private listener, so their connections can't be mistaken for clients
+        let node_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let node_addr = node_listener.local_addr()?;
+
+        let mut children = Vec::new();
+        for _ in 0..config.nodes {
+            let mut cmd = Command::new(&config.bin);
+            cmd.arg("--connect").arg(node_addr.to_string()).kill_on_drop(true);
+            if !config.verbose {
+                cmd.stderr(std::process::Stdio::null());
+            }
+            children.push(cmd.spawn()?);
+        }
+
+        // Node IDs are handed out in the order the nodes connect
+        let node_ids: Vec<String> = (0..config.nodes).map(|i| format!("n{}", i)).collect();
+        for id in &node_ids {
+            let (stream, _) = tokio::time::timeout(STARTUP_TIMEOUT, node_listener.accept())
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "node never connected"))??;
+            attach(&routes, LineTransport::tcp(stream), Some(id.clone()));
+        }
+
+        // Answers to the handshake come back to us
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        routes.lock().unwrap().insert(LAUNCHER.to_string(), tx);
+
+        let topology = grid(&node_ids);
+        let mut msg_id = 0;
+        for id in &node_ids {
+            for body in [
+                json!({"type": "init", "node_id": id, "node_ids": node_ids}),
+                json!({"type": "topology", "topology": topology}),
+            ] {
+                msg_id += 1;
+                let mut body = body;
+                body["msg_id"] = json!(msg_id);
+                route(&routes, id, json!({"src": LAUNCHER, "dest": id, "body": body}).to_string());
+
+                let reply = tokio::time::timeout(STARTUP_TIMEOUT, rx.recv())
+                    .await
+                    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, format!("{} never answered the handshake", id)))?;
+                info!("{} answered {}", id, reply.unwrap_or_default());
+            }
+        }
+
+        // Only now open the door to clients
+        let client_listener = TcpListener::bind(config.client_addr).await?;
+        let client_addr = client_listener.local_addr()?;
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = client_listener.accept().await {
+                attach(&routes, LineTransport::tcp(stream), None);
+            }
+        });
+
+        Ok(Cluster {
+            client_addr,
+            node_ids,
+            _children: children,
+        })
+    }
+
+    // Where clients should connect
+    pub fn client_addr(&self) -> SocketAddr {
+        self.client_addr
+    }
+
+    pub fn node_ids(&self) -> &[String] {
+        &self.node_ids
+    }
+}
+
+// Wires a connection into the switchboard: everything it sends is routed by dest, and
+// everything routed to it is written out. Clients have no name up front, so they are known
+// by whichever src they send from.
+fn attach(routes: &Routes, transport: impl Transport, name: Option<String>) {
+    let (mut inbound, mut outbound) = transport.split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    if let Some(name) = &name {
+        routes.lock().unwrap().insert(name.clone(), tx.clone());
+    }
+
+    tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            if outbound.send(line).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let routes = routes.clone();
+    tokio::spawn(async move {
+        let mut seen_as = Vec::new();
+        while let Ok(Some(line)) = inbound.recv().await {
+            let msg: Message<Value> = match serde_json::from_str(&line) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    warn!("dropping malformed message {}: {}", line, e);
+                    continue;
+                },
+            };
+
+            if name.is_none() && !seen_as.contains(&msg.src) {
+                routes.lock().unwrap().insert(msg.src.clone(), tx.clone());
+                seen_as.push(msg.src.clone());
+            }
+            route(&routes, &msg.dest, line);
+        }
+
+        // Forget a client once it hangs up, unless a newer connection has taken its src over
+        let mut routes = routes.lock().unwrap();
+        for src in seen_as {
+            if routes.get(&src).is_some_and(|route| route.same_channel(&tx)) {
+                routes.remove(&src);
+            }
+        }
+    });
+}
+
+// Hands a message to whoever is at dest
+fn route(routes: &Routes, dest: &str, line: String) {
+    match routes.lock().unwrap().get(dest) {
+        Some(tx) => {
+            let _ = tx.send(line);
+        },
+        None => warn!("no route to {}, dropping {}", dest, line),
+    }
+}
+
+// The same kind of layout Maelstrom suggests: nodes in a square-ish grid, linked to the
+// nodes above, below, left and right of them
+pub fn grid(node_ids: &[String]) -> HashMap<String, Vec<String>> {
+    let width = (node_ids.len() as f64).sqrt().ceil().max(1.0) as usize;
+    let mut topology = HashMap::new();
+    for (i, id) in node_ids.iter().enumerate() {
+        let mut neighbors = Vec::new();
+        if i % width > 0 {
+            neighbors.push(node_ids[i - 1].clone());
+        }
+        if i % width + 1 < width && i + 1 < node_ids.len() {
+            neighbors.push(node_ids[i + 1].clone());
+        }
+        if i >= width {
+            neighbors.push(node_ids[i - width].clone());
+        }
+        if i + width < node_ids.len() {
+            neighbors.push(node_ids[i + width].clone());
+        }
+        topology.insert(id.clone(), neighbors);
+    }
+    topology
+}
+
+// Sends one request into a running cluster as a client and returns the reply body
+pub async fn call(addr: SocketAddr, dest: &str, mut body: Value) -> io::Result<Value> {
+    let (mut inbound, mut outbound) = LineTransport::tcp(TcpStream::connect(addr).await?).split();
+
+    // Each call gets a src of its own, so concurrent calls never take each other's replies
+    let src = format!("c{}-{}", std::process::id(), NEXT_CALL.fetch_add(1, Ordering::Relaxed));
+    body["msg_id"] = json!(1);
+    outbound.send(json!({"src": src, "dest": dest, "body": body}).to_string()).await?;
+
+    while let Some(line) = inbound.recv().await? {
+        let reply: Message<Value> = serde_json::from_str(&line)?;
+        if reply.body.in_reply_to == Some(1) {
+            return Ok(reply.body.payload);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::UnexpectedEof, "cluster hung up before replying"))
+}
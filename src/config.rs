@@ -0,0 +1,117 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::message_set::Storage;
+use crate::topology::Topology;
+use crate::workloads::WorkloadSet;
+
+// Startup settings, read from the environment so they can be tuned without a rebuild
+#[derive(Clone, Debug)]
+pub struct Config {
+    // Which workloads the node serves
+    pub workloads: WorkloadSet,
+
+    // How often buffered values are flushed out to our neighbors, never zero
+    pub gossip_interval: Duration,
+
+    // How long to wait for a gossip_ok before the first retransmission, never zero
+    pub retry_timeout: Duration,
+
+    // Upper bound on the exponential backoff between retransmissions, never zero
+    pub retry_max_backoff: Duration,
+
+    // How many times a delivery is attempted before we give up on it, 0 for forever
+    pub retry_max_attempts: u32,
+
+    // How often we reconcile our whole message set with one neighbor, zero to disable
+    pub anti_entropy_interval: Duration,
+
+    // How we choose our neighbors
+    pub topology: Topology,
+
+    // Where to keep messages on disk so they survive a restart, in memory only if unset
+    pub data_dir: Option<PathBuf>,
+
+    // How often the on-disk log is folded into a snapshot
+    pub snapshot_interval: Duration,
+
+    // How often our counters are written to the log, zero to disable
+    pub stats_interval: Duration,
+
+    // How the broadcast layer keeps the values it holds
+    pub storage: Storage,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            workloads: WorkloadSet::default(),
+            gossip_interval: Duration::from_millis(100),
+            retry_timeout: Duration::from_millis(500),
+            retry_max_backoff: Duration::from_millis(5000),
+            retry_max_attempts: 0,
+            anti_entropy_interval: Duration::from_millis(1000),
+            topology: Topology::Maelstrom,
+            data_dir: None,
+            snapshot_interval: Duration::from_millis(10000),
+            stats_interval: Duration::from_millis(10000),
+            storage: Storage::Json,
+        }
+    }
+}
+
+impl Config {
+    // Fails only on a bad WORKLOADS, since quietly serving the wrong workloads is worse than
+    // not starting; anything else invalid falls back to its default
+    pub fn from_env() -> Result<Config, String> {
+        let defaults = Config::default();
+        Ok(Config {
+            workloads: env_parse("WORKLOADS", defaults.workloads)?,
+            gossip_interval: env_nonzero_ms("GOSSIP_INTERVAL_MS", defaults.gossip_interval),
+            retry_timeout: env_nonzero_ms("RETRY_TIMEOUT_MS", defaults.retry_timeout),
+            retry_max_backoff: env_nonzero_ms("RETRY_MAX_BACKOFF_MS", defaults.retry_max_backoff),
+            retry_max_attempts: env_or("RETRY_MAX_ATTEMPTS", defaults.retry_max_attempts),
+            anti_entropy_interval: env_ms("ANTI_ENTROPY_INTERVAL_MS", defaults.anti_entropy_interval),
+            topology: env_or("TOPOLOGY", defaults.topology),
+            data_dir: env::var_os("DATA_DIR").map(PathBuf::from).or(defaults.data_dir),
+            snapshot_interval: env_ms("SNAPSHOT_INTERVAL_MS", defaults.snapshot_interval),
+            stats_interval: env_ms("STATS_INTERVAL_MS", defaults.stats_interval),
+            storage: env_or("STORAGE", defaults.storage),
+        })
+    }
+}
+
+// Reads a setting from the environment, falling back to a default when unset or invalid
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+// Reads a setting from the environment that has to be valid if it is set at all
+fn env_parse<T: std::str::FromStr<Err = String>>(key: &str, default: T) -> Result<T, String> {
+    match env::var(key) {
+        Ok(v) => v.parse().map_err(|e| format!("{}: {}", key, e)),
+        Err(_) => Ok(default),
+    }
+}
+
+// Reads a millisecond duration from the environment
+fn env_ms(key: &str, default: Duration) -> Duration {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(default)
+}
+
+// Reads a millisecond duration that drives a timer loop, where zero would spin, so zero is
+// treated as invalid too
+fn env_nonzero_ms(key: &str, default: Duration) -> Duration {
+    match env_ms(key, default) {
+        d if d.is_zero() => default,
+        d => d,
+    }
+}
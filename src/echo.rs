@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::maelstrom_node::{ErrorCode, Handler, Message, RpcError, Runtime};
+
+// Every kind of message the echo workload sends or receives, tagged by its "type" field
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Payload {
+    Echo {
+        echo: Value,
+    },
+    EchoOk {
+        echo: Value,
+    },
+
+    // Anything with a type we don't recognize
+    #[serde(other)]
+    Unknown,
+}
+
+// The echo workload: sends back whatever it's given
+#[derive(Debug, Default)]
+pub struct Echo;
+
+impl Handler for Echo {
+    type Payload = Payload;
+
+    fn handle(&mut self, _rt: &Runtime, msg: &Message<Payload>) -> Result<Option<Payload>, RpcError> {
+        match &msg.body.payload {
+            Payload::Echo { echo } => Ok(Some(Payload::EchoOk { echo: echo.clone() })),
+            Payload::EchoOk { .. } | Payload::Unknown => Err(RpcError::new(ErrorCode::NotSupported, "unsupported message type")),
+        }
+    }
+}
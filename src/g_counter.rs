@@ -0,0 +1,219 @@
+// A grow-only counter: each node counts its own adds, and the total is the sum over nodes.
+// The per-node map is the whole state, and it stays as small as the cluster. Nodes gossip it
+// to their neighbors whenever it grows, and merging is taking the highest count seen per
+// node, so a map can arrive late, twice or not at all. Anything lost is made good by
+// periodically resending the map to one neighbor at a time.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use crate::config::Config;
+use crate::maelstrom_node::{ErrorCode, Handler, Message, RpcError, Runtime, Timer};
+use crate::store::Store;
+
+// Every kind of message the g-counter workload sends or receives, tagged by its "type" field
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Payload {
+    Add {
+        delta: u64,
+    },
+    AddOk,
+    Read,
+    ReadOk {
+        value: u64,
+    },
+    Topology {
+        topology: HashMap<String, Vec<String>>,
+    },
+    TopologyOk,
+
+    // The sender's view of every node's count
+    Counts {
+        counts: BTreeMap<String, u64>,
+    },
+
+    // Anything with a type we don't recognize
+    #[serde(other)]
+    Unknown,
+}
+
+// A node's count as kept on disk
+#[derive(Serialize, Deserialize, Debug)]
+struct Count {
+    node: String,
+    count: u64,
+}
+
+#[derive(Debug)]
+pub struct GCounter {
+    config: Config,
+    neighbors: Vec<String>,
+
+    // The highest count we know of for each node, ours included
+    counts: BTreeMap<String, u64>,
+
+    // Whether counts have grown since we last gossiped them
+    dirty: bool,
+
+    // Our own count as of the last time we wrote it down
+    persisted: u64,
+
+    // Round-robin position in our neighbor list for resends
+    resend_cursor: usize,
+
+    // Our on-disk copy, once init has told us who we are, if we have a data directory
+    store: Option<Store>,
+}
+
+impl GCounter {
+    pub fn new(config: Config) -> GCounter {
+        GCounter {
+            config,
+            neighbors: Vec::new(),
+            counts: BTreeMap::new(),
+            dirty: false,
+            persisted: 0,
+            resend_cursor: 0,
+            store: None,
+        }
+    }
+
+    // The counter's value as far as we know
+    pub fn value(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    // Takes the highest count per node from another view, noting whether anything grew
+    fn merge(&mut self, counts: &BTreeMap<String, u64>) {
+        for (node, count) in counts {
+            let known = self.counts.entry(node.clone()).or_default();
+            if *count > *known {
+                *known = *count;
+                self.dirty = true;
+            }
+        }
+    }
+
+    // Writes our own count down if it has grown, then gossips the map if anything changed
+    fn flush(&mut self, rt: &Runtime) -> Result<(), RpcError> {
+        let count = self.counts.get(rt.id()).copied().unwrap_or_default();
+        if count != self.persisted {
+            if let Some(store) = &mut self.store {
+                store
+                    .append(&[json!(Count { node: rt.id().to_string(), count })])
+                    .map_err(|e| RpcError::new(ErrorCode::Crash, format!("could not persist count: {}", e)))?;
+            }
+            self.persisted = count;
+        }
+
+        if !self.dirty {
+            return Ok(());
+        }
+        for n in self.neighbors.iter().filter(|n| *n != rt.id()) {
+            rt.send(n.clone(), Payload::Counts { counts: self.counts.clone() })?;
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    // Sends the whole map to the next neighbor, whether or not it has changed
+    fn resend(&mut self, rt: &Runtime) -> Result<(), RpcError> {
+        let peers: Vec<&String> = self.neighbors.iter().filter(|n| *n != rt.id()).collect();
+        if peers.is_empty() || self.counts.is_empty() {
+            return Ok(());
+        }
+
+        let dest = peers[self.resend_cursor % peers.len()].clone();
+        self.resend_cursor = self.resend_cursor.wrapping_add(1);
+        rt.send(dest, Payload::Counts { counts: self.counts.clone() })?;
+        Ok(())
+    }
+
+    // Replaces the on-disk log with one entry per node
+    fn compact(&mut self, _rt: &Runtime) -> Result<(), RpcError> {
+        if let Some(store) = self.store.as_mut().filter(|s| s.is_dirty()) {
+            let counts: Vec<_> = self
+                .counts
+                .iter()
+                .map(|(node, count)| json!(Count { node: node.clone(), count: *count }))
+                .collect();
+            store
+                .compact(&counts)
+                .map_err(|e| RpcError::new(ErrorCode::Crash, format!("could not compact store: {}", e)))?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for GCounter {
+    type Payload = Payload;
+
+    // Maelstrom never sends this workload a topology, so we pick our own neighbors. Counts
+    // recovered from disk include our own, so we carry on from where we were.
+    fn init(&mut self, rt: &Runtime) -> Result<(), RpcError> {
+        self.neighbors = self.config.topology.default_neighbors(rt.id(), rt.node_ids());
+
+        let Some(dir) = self.config.data_dir.clone() else {
+            return Ok(());
+        };
+
+        let (store, values) = Store::open(&dir, rt.id())
+            .map_err(|e| RpcError::new(ErrorCode::Crash, format!("could not open store in {}: {}", dir.display(), e)))?;
+        let mut counts = BTreeMap::new();
+        for v in values {
+            if let Ok(Count { node, count }) = serde_json::from_value(v) {
+                let known: &mut u64 = counts.entry(node).or_default();
+                *known = (*known).max(count);
+            }
+        }
+        self.merge(&counts);
+        self.persisted = self.counts.get(rt.id()).copied().unwrap_or_default();
+        info!("recovered counts for {} nodes from {}", self.counts.len(), dir.display());
+
+        self.store = Some(store);
+        Ok(())
+    }
+
+    fn handle(&mut self, rt: &Runtime, msg: &Message<Payload>) -> Result<Option<Payload>, RpcError> {
+        let reply = match &msg.body.payload {
+            Payload::Add { delta } => {
+                if *delta > 0 {
+                    *self.counts.entry(rt.id().to_string()).or_default() += delta;
+                    self.dirty = true;
+                }
+                Payload::AddOk
+            },
+            Payload::Read => Payload::ReadOk { value: self.value() },
+            Payload::Topology { topology } => {
+                self.neighbors = self.config.topology.suggested_neighbors(rt.id(), rt.node_ids(), topology)?;
+                Payload::TopologyOk
+            },
+            Payload::Counts { counts } => {
+                // Whatever grew goes out to our own neighbors on the next flush
+                self.merge(counts);
+                return Ok(None);
+            },
+            Payload::AddOk | Payload::ReadOk { .. } | Payload::TopologyOk | Payload::Unknown => {
+                return Err(RpcError::new(ErrorCode::NotSupported, "unsupported message type"));
+            },
+        };
+        Ok(Some(reply))
+    }
+
+    // Gossiping as often as the broadcast workload flushes, with resends on its anti-entropy
+    // interval
+    fn timers(&self) -> Vec<Timer<GCounter>> {
+        let mut timers = vec![Timer::new(self.config.gossip_interval, GCounter::flush)];
+        if !self.config.anti_entropy_interval.is_zero() {
+            timers.push(Timer::new(self.config.anti_entropy_interval, GCounter::resend));
+        }
+        if self.config.data_dir.is_some() {
+            timers.push(Timer::new(self.config.snapshot_interval, GCounter::compact));
+        }
+        timers
+    }
+}
@@ -0,0 +1,213 @@
+// A Kafka-style log: clients append to named logs and poll them by offset, and commit how
+// far they've read. Every key is owned by one node, picked by hashing the key over the
+// cluster, and only that node holds its log, so offsets never need agreeing on. Any node
+// can take a request; keys it doesn't own are forwarded to their owners.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::task::JoinSet;
+
+use crate::maelstrom_node::{ErrorCode, Handler, Message, RpcError, Runtime};
+use crate::rng::hash_bytes;
+
+// Offsets per key
+pub type Offsets = BTreeMap<String, u64>;
+
+// Every kind of message the kafka workload sends or receives, tagged by its "type" field
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Payload {
+    Send {
+        key: String,
+        msg: Value,
+    },
+    SendOk {
+        offset: u64,
+    },
+    // Everything in each log from the given offset on
+    Poll {
+        offsets: Offsets,
+    },
+    PollOk {
+        msgs: BTreeMap<String, Vec<(u64, Value)>>,
+    },
+    CommitOffsets {
+        offsets: Offsets,
+    },
+    CommitOffsetsOk,
+    ListCommittedOffsets {
+        keys: Vec<String>,
+    },
+    ListCommittedOffsetsOk {
+        offsets: Offsets,
+    },
+
+    // Anything with a type we don't recognize
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Default)]
+pub struct Kafka {
+    // The logs we own, where a message's offset is its index
+    logs: BTreeMap<String, Vec<Value>>,
+    committed: Offsets,
+}
+
+impl Kafka {
+    pub fn new() -> Kafka {
+        Kafka::default()
+    }
+
+    // Serves the part of a request whose keys we own
+    fn serve(&mut self, payload: &Payload) -> Result<Payload, RpcError> {
+        let reply = match payload {
+            Payload::Send { key, msg } => {
+                let log = self.logs.entry(key.clone()).or_default();
+                log.push(msg.clone());
+                Payload::SendOk {
+                    offset: log.len() as u64 - 1,
+                }
+            },
+            Payload::Poll { offsets } => {
+                let mut msgs = BTreeMap::new();
+                for (key, from) in offsets {
+                    if let Some(log) = self.logs.get(key) {
+                        let tail = log.iter().enumerate().skip(*from as usize).map(|(i, v)| (i as u64, v.clone()));
+                        msgs.insert(key.clone(), tail.collect());
+                    }
+                }
+                Payload::PollOk { msgs }
+            },
+            Payload::CommitOffsets { offsets } => {
+                // Commits only move forward, so a late or repeated one can't undo a newer one
+                for (key, offset) in offsets {
+                    let committed = self.committed.entry(key.clone()).or_default();
+                    *committed = (*committed).max(*offset);
+                }
+                Payload::CommitOffsetsOk
+            },
+            Payload::ListCommittedOffsets { keys } => Payload::ListCommittedOffsetsOk {
+                offsets: keys
+                    .iter()
+                    .filter_map(|k| self.committed.get(k).map(|o| (k.clone(), *o)))
+                    .collect(),
+            },
+            Payload::SendOk { .. }
+            | Payload::PollOk { .. }
+            | Payload::CommitOffsetsOk
+            | Payload::ListCommittedOffsetsOk { .. }
+            | Payload::Unknown => {
+                return Err(RpcError::new(ErrorCode::NotSupported, "unsupported message type"));
+            },
+        };
+        Ok(reply)
+    }
+}
+
+impl Handler for Kafka {
+    type Payload = Payload;
+
+    // Serves what we own straight away. If any keys belong to other nodes, the request is
+    // split up and forwarded, and the client answered once every owner has replied.
+    fn handle(&mut self, rt: &Runtime, msg: &Message<Payload>) -> Result<Option<Payload>, RpcError> {
+        // A reply to a forward we already gave up on
+        if msg.body.in_reply_to.is_some() {
+            return Ok(None);
+        }
+
+        let (ours, theirs) = split(rt, &msg.body.payload);
+        let mut reply = ours.map(|ours| self.serve(&ours)).transpose()?;
+        if theirs.is_empty() {
+            return Ok(reply);
+        }
+
+        // Requests go out now, and the replies are awaited side by side in the background.
+        // The first failure answers the client, and dropping the set abandons the rest.
+        let mut pending = JoinSet::new();
+        for (owner, part) in theirs {
+            pending.spawn(rt.rpc::<Payload, Payload>(owner, part));
+        }
+        let (rt, msg) = (rt.clone(), msg.clone());
+        tokio::spawn(async move {
+            while let Some(part) = pending.join_next().await {
+                let part = part.map_err(|e| RpcError::new(ErrorCode::Crash, e.to_string())).and_then(|r| r);
+                match part {
+                    Ok(body) => merge(&mut reply, body.payload),
+                    Err(e) => {
+                        let _ = rt.reply_error(&msg, e);
+                        return;
+                    },
+                }
+            }
+            if let Some(reply) = reply {
+                let _ = rt.reply(&msg, reply);
+            }
+        });
+        Ok(None)
+    }
+}
+
+// The node that owns a key
+fn owner<'a>(rt: &'a Runtime, key: &str) -> &'a str {
+    let ids = rt.node_ids();
+    if ids.is_empty() {
+        return rt.id();
+    }
+    &ids[(hash_bytes(key.as_bytes()) % ids.len() as u64) as usize]
+}
+
+// Splits a request into the part we own, if any, and a part for each other owner
+fn split(rt: &Runtime, payload: &Payload) -> (Option<Payload>, BTreeMap<String, Payload>) {
+    let mut theirs = BTreeMap::new();
+    let ours = match payload {
+        Payload::Send { key, .. } if owner(rt, key) != rt.id() => {
+            theirs.insert(owner(rt, key).to_string(), payload.clone());
+            return (None, theirs);
+        },
+        Payload::Poll { offsets } => Payload::Poll {
+            offsets: split_keys(rt, offsets.clone(), &mut theirs, |offsets| Payload::Poll { offsets }),
+        },
+        Payload::CommitOffsets { offsets } => Payload::CommitOffsets {
+            offsets: split_keys(rt, offsets.clone(), &mut theirs, |offsets| Payload::CommitOffsets { offsets }),
+        },
+        Payload::ListCommittedOffsets { keys } => {
+            let keys: Offsets = keys.iter().map(|k| (k.clone(), 0)).collect();
+            let ours = split_keys(rt, keys, &mut theirs, |keys| Payload::ListCommittedOffsets {
+                keys: keys.into_keys().collect(),
+            });
+            Payload::ListCommittedOffsets {
+                keys: ours.into_keys().collect(),
+            }
+        },
+        other => other.clone(),
+    };
+    (Some(ours), theirs)
+}
+
+// Keeps the entries we own and files the rest under their owners as requests built by `make`
+fn split_keys(rt: &Runtime, offsets: Offsets, theirs: &mut BTreeMap<String, Payload>, make: fn(Offsets) -> Payload) -> Offsets {
+    let mut ours = Offsets::new();
+    let mut by_owner: BTreeMap<String, Offsets> = BTreeMap::new();
+    for (key, offset) in offsets {
+        match owner(rt, &key) {
+            id if id == rt.id() => ours.insert(key, offset),
+            id => by_owner.entry(id.to_string()).or_default().insert(key, offset),
+        };
+    }
+    theirs.extend(by_owner.into_iter().map(|(id, offsets)| (id, make(offsets))));
+    ours
+}
+
+// Folds an owner's reply into the one we're building. Owners cover disjoint keys, so
+// merging is just collecting.
+fn merge(into: &mut Option<Payload>, from: Payload) {
+    match (into, from) {
+        (Some(Payload::PollOk { msgs }), Payload::PollOk { msgs: more }) => msgs.extend(more),
+        (Some(Payload::ListCommittedOffsetsOk { offsets }), Payload::ListCommittedOffsetsOk { offsets: more }) => offsets.extend(more),
+        (into @ None, from) => *into = Some(from),
+        _ => {},
+    }
+}
@@ -0,0 +1,14 @@
+pub mod broadcast;
+pub mod cluster;
+pub mod config;
+pub mod echo;
+pub mod g_counter;
+pub mod kafka;
+pub mod maelstrom_node;
+pub mod message_set;
+pub mod rng;
+pub mod store;
+pub mod topology;
+pub mod txn;
+pub mod unique_ids;
+pub mod workloads;
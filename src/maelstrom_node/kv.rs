@@ -0,0 +1,152 @@
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{ErrorCode, RpcError, Runtime};
+
+// The messages Maelstrom's key-value services understand, see
+// https://github.com/jepsen-io/maelstrom/blob/main/doc/services.md
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KvPayload {
+    Read {
+        key: Value,
+    },
+    ReadOk {
+        value: Value,
+    },
+    Write {
+        key: Value,
+        value: Value,
+    },
+    WriteOk,
+    Cas {
+        key: Value,
+        from: Value,
+        to: Value,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        create_if_not_exists: bool,
+    },
+    CasOk,
+}
+
+// Why a key-value operation failed. The two outcomes callers usually branch on get their own
+// variants; everything else, timeouts included, is passed through as it came.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum KvError {
+    KeyDoesNotExist(String),
+    PreconditionFailed(String),
+    Other(RpcError),
+}
+
+impl From<RpcError> for KvError {
+    fn from(e: RpcError) -> KvError {
+        match e.code {
+            ErrorCode::KeyDoesNotExist => KvError::KeyDoesNotExist(e.text),
+            ErrorCode::PreconditionFailed => KvError::PreconditionFailed(e.text),
+            _ => KvError::Other(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for KvError {
+    fn from(e: serde_json::Error) -> KvError {
+        KvError::Other(e.into())
+    }
+}
+
+// So handlers can pass KV failures straight back to their own clients
+impl From<KvError> for RpcError {
+    fn from(e: KvError) -> RpcError {
+        match e {
+            KvError::KeyDoesNotExist(text) => RpcError::new(ErrorCode::KeyDoesNotExist, text),
+            KvError::PreconditionFailed(text) => RpcError::new(ErrorCode::PreconditionFailed, text),
+            KvError::Other(e) => e,
+        }
+    }
+}
+
+impl fmt::Display for KvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KvError::KeyDoesNotExist(text) => write!(f, "key does not exist: {}", text),
+            KvError::PreconditionFailed(text) => write!(f, "precondition failed: {}", text),
+            KvError::Other(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for KvError {}
+
+// A client for one of Maelstrom's key-value services. Cheap to clone, so a handler can hand
+// one to each task it spawns.
+#[derive(Clone, Debug)]
+pub struct Kv {
+    rt: Runtime,
+    service: &'static str,
+}
+
+impl Kv {
+    // Sequentially consistent
+    pub fn seq(rt: &Runtime) -> Kv {
+        Kv::new(rt, "seq-kv")
+    }
+
+    // Linearizable
+    pub fn lin(rt: &Runtime) -> Kv {
+        Kv::new(rt, "lin-kv")
+    }
+
+    // Last-write-wins, which may lose writes and serve stale reads
+    pub fn lww(rt: &Runtime) -> Kv {
+        Kv::new(rt, "lww-kv")
+    }
+
+    pub fn new(rt: &Runtime, service: &'static str) -> Kv {
+        Kv { rt: rt.clone(), service }
+    }
+
+    // Reads a key, failing with KeyDoesNotExist if it was never written
+    pub async fn read<K: Serialize, T: DeserializeOwned>(&self, key: K) -> Result<T, KvError> {
+        let key = serde_json::to_value(key)?;
+        match self.call(KvPayload::Read { key }).await? {
+            KvPayload::ReadOk { value } => Ok(serde_json::from_value(value)?),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn write<K: Serialize, T: Serialize>(&self, key: K, value: T) -> Result<(), KvError> {
+        let (key, value) = (serde_json::to_value(key)?, serde_json::to_value(value)?);
+        match self.call(KvPayload::Write { key, value }).await? {
+            KvPayload::WriteOk => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    // Sets a key to `to` only if it currently holds `from`, failing with PreconditionFailed
+    // otherwise. With create_if_not_exists, a missing key counts as holding `from`.
+    pub async fn cas<K: Serialize, T: Serialize>(&self, key: K, from: T, to: T, create_if_not_exists: bool) -> Result<(), KvError> {
+        let payload = KvPayload::Cas {
+            key: serde_json::to_value(key)?,
+            from: serde_json::to_value(from)?,
+            to: serde_json::to_value(to)?,
+            create_if_not_exists,
+        };
+        match self.call(payload).await? {
+            KvPayload::CasOk => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    async fn call(&self, payload: KvPayload) -> Result<KvPayload, KvError> {
+        let reply = self.rt.rpc(self.service, payload).await?;
+        Ok(reply.payload)
+    }
+}
+
+// A reply of the wrong type means the service is not what we think it is
+fn unexpected(reply: KvPayload) -> KvError {
+    KvError::Other(RpcError::new(ErrorCode::Crash, format!("unexpected reply {:?}", reply)))
+}
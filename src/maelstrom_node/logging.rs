@@ -0,0 +1,22 @@
+use std::env;
+use std::io;
+
+use tracing_subscriber::EnvFilter;
+
+// Sends logs to STDERR, where Maelstrom collects them; STDOUT belongs to the protocol.
+//
+// LOG_LEVEL takes a level or a full filter (`debug`, `warn,maelstrom_broadcast::broadcast=trace`)
+// and defaults to `info`. LOG_FORMAT=json writes one JSON object per event, for post-processing.
+pub fn init_logging() {
+    let filter = env::var("LOG_LEVEL")
+        .ok()
+        .and_then(|level| EnvFilter::try_new(level).ok())
+        .unwrap_or_else(|| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_writer(io::stderr).with_ansi(false);
+
+    // Someone may have beaten us to it, as tests do, in which case theirs wins
+    let _ = match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().with_current_span(true).try_init(),
+        _ => builder.try_init(),
+    };
+}
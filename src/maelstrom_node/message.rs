@@ -0,0 +1,60 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::ErrorCode;
+
+// The envelope every Maelstrom message travels in, generic over the workload's payload type
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Message<P> {
+    pub src: String,
+    pub dest: String,
+    pub body: MessageBody<P>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct MessageBody<P> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msg_id: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<u64>,
+
+    #[serde(flatten)]
+    pub payload: P,
+}
+
+// Payloads the runtime deals with itself, whatever the workload
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuntimePayload {
+    Init {
+        node_id: String,
+        node_ids: Vec<String>,
+    },
+    InitOk,
+    Error {
+        code: ErrorCode,
+        text: String,
+    },
+}
+
+impl Message<Value> {
+    // The "type" field of a message we haven't decoded yet
+    pub fn msg_type(&self) -> &str {
+        self.body.payload.get("type").and_then(Value::as_str).unwrap_or_default()
+    }
+
+    // Decodes the payload into a concrete type, keeping the envelope as it was
+    pub fn decode<P: DeserializeOwned>(self) -> serde_json::Result<Message<P>> {
+        Ok(Message {
+            src: self.src,
+            dest: self.dest,
+            body: MessageBody {
+                msg_id: self.body.msg_id,
+                in_reply_to: self.body.in_reply_to,
+                payload: serde_json::from_value(self.body.payload)?,
+            },
+        })
+    }
+}
@@ -0,0 +1,18 @@
+// The plumbing every Maelstrom workload needs: the message envelope, the init handshake,
+// msg_id allocation, I/O over a pluggable Transport and dispatch to a Handler. Workloads
+// only implement Handler.
+
+mod error;
+mod kv;
+mod logging;
+mod message;
+mod runtime;
+mod transport;
+mod type_of;
+
+pub use error::{ErrorCode, RpcError};
+pub use kv::{Kv, KvError, KvPayload};
+pub use logging::init_logging;
+pub use message::{Message, MessageBody, RuntimePayload};
+pub use runtime::{Handler, Node, Runtime, Task, Timer, Traffic, DEFAULT_RPC_TIMEOUT};
+pub use transport::{channel, ChannelTransport, Inbound, LineReader, LineTransport, LineWriter, Outbound, Transport};
@@ -0,0 +1,523 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::future::Future;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+use tracing::{debug, error, info_span, warn};
+
+use super::transport::{Inbound, LineTransport, Outbound, Transport};
+use super::type_of::type_of;
+use super::{ErrorCode, Message, MessageBody, RpcError, RuntimePayload};
+
+// How long rpc waits for a reply before giving up
+pub const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_secs(1);
+
+// A workload running on top of the runtime
+pub trait Handler: Sized {
+    // The messages this workload understands, usually a `#[serde(tag = "type")]` enum
+    type Payload: Serialize + DeserializeOwned + Debug;
+
+    // Called once the init handshake has told us who we are, before init_ok goes out
+    fn init(&mut self, _rt: &Runtime) -> Result<(), RpcError> {
+        Ok(())
+    }
+
+    // Serves one inbound message. Whatever comes back is sent as the reply if the message
+    // was a request; an error becomes a Maelstrom error reply.
+    fn handle(&mut self, rt: &Runtime, msg: &Message<Self::Payload>) -> Result<Option<Self::Payload>, RpcError>;
+
+    // Periodic background work, run in between messages
+    fn timers(&self) -> Vec<Timer<Self>> {
+        Vec::new()
+    }
+}
+
+// Background work run against a handler, usually one of its methods
+pub type Task<H> = Box<dyn Fn(&mut H, &Runtime) -> Result<(), RpcError> + Send>;
+
+// A handler method to call on a fixed interval
+pub struct Timer<H> {
+    pub every: Duration,
+    pub run: Task<H>,
+}
+
+impl<H> Timer<H> {
+    pub fn new(every: Duration, run: impl Fn(&mut H, &Runtime) -> Result<(), RpcError> + Send + 'static) -> Timer<H> {
+        Timer {
+            every,
+            run: Box::new(run),
+        }
+    }
+
+    // Rebinds the timer to run against a handler that contains this one, doing nothing
+    // whenever `get` comes back empty
+    pub fn lift<O>(self, get: fn(&mut O) -> Option<&mut H>) -> Timer<O>
+    where
+        H: 'static,
+        O: 'static,
+    {
+        let run = self.run;
+        Timer::new(self.every, move |outer: &mut O, rt: &Runtime| match get(outer) {
+            Some(handler) => run(handler, rt),
+            None => Ok(()),
+        })
+    }
+}
+
+// Who we are, as told to us by the init message
+#[derive(Debug)]
+struct NodeInfo {
+    id: String,
+    node_ids: Vec<String>,
+}
+
+// What has gone over the wire so far, counted per message type
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Traffic {
+    pub received: BTreeMap<String, u64>,
+    pub sent: BTreeMap<String, u64>,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+}
+
+#[derive(Debug)]
+struct Inner {
+    node: OnceLock<NodeInfo>,
+    msg_id: AtomicU64,
+
+    // Outstanding rpc calls, keyed by the msg_id their request went out with
+    callbacks: Mutex<HashMap<u64, oneshot::Sender<Message<Value>>>>,
+
+    // Serialized lines headed for STDOUT
+    out: mpsc::UnboundedSender<String>,
+
+    traffic: Mutex<Traffic>,
+}
+
+// Owns our identity, msg_id allocation and the outbound side of I/O. Cheap to clone, so
+// background tasks can hold on to one.
+#[derive(Clone, Debug)]
+pub struct Runtime {
+    inner: Arc<Inner>,
+}
+
+impl Runtime {
+    pub fn new(out: mpsc::UnboundedSender<String>) -> Runtime {
+        Runtime {
+            inner: Arc::new(Inner {
+                node: OnceLock::new(),
+                msg_id: AtomicU64::new(0),
+                callbacks: Mutex::new(HashMap::new()),
+                out,
+                traffic: Mutex::new(Traffic::default()),
+            }),
+        }
+    }
+
+    // Our node ID, empty until init
+    pub fn id(&self) -> &str {
+        self.inner.node.get().map(|n| n.id.as_str()).unwrap_or_default()
+    }
+
+    // Every node in the cluster, including us, empty until init
+    pub fn node_ids(&self) -> &[String] {
+        self.inner.node.get().map(|n| n.node_ids.as_slice()).unwrap_or_default()
+    }
+
+    // A snapshot of our message counts so far
+    pub fn traffic(&self) -> Traffic {
+        self.inner.traffic.lock().unwrap().clone()
+    }
+
+    // Convenience function for responding to a message with a reply
+    pub fn reply<Q, P: Serialize>(&self, request: &Message<Q>, payload: P) -> serde_json::Result<()> {
+        let body = MessageBody {
+            msg_id: None,
+            in_reply_to: request.body.msg_id,
+            payload,
+        };
+        self.send_body(request.src.clone(), body)?;
+        Ok(())
+    }
+
+    // Sends a new message to a specific destination, returning the msg_id it went out with
+    pub fn send<P: Serialize>(&self, dest: impl Into<String>, payload: P) -> serde_json::Result<u64> {
+        let body = MessageBody {
+            msg_id: None,
+            in_reply_to: None,
+            payload,
+        };
+        self.send_body(dest.into(), body)
+    }
+
+    // Sends a request and waits for the matching reply, failing with a timeout error if none
+    // arrives within DEFAULT_RPC_TIMEOUT. An error reply comes back as Err.
+    pub fn rpc<P, R>(&self, dest: impl Into<String>, payload: P) -> impl Future<Output = Result<MessageBody<R>, RpcError>>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        self.rpc_with_timeout(dest, payload, DEFAULT_RPC_TIMEOUT)
+    }
+
+    // Same as rpc, with a caller-chosen timeout
+    pub fn rpc_with_timeout<P, R>(
+        &self,
+        dest: impl Into<String>,
+        payload: P,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<MessageBody<R>, RpcError>>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        // Register the callback before sending, so even an instant reply finds it. The guard
+        // goes with the future, so the callback is forgotten however the future ends, even if
+        // it is dropped without ever being polled.
+        let msg_id = self.next_msg_id();
+        let (tx, rx) = oneshot::channel();
+        self.inner.callbacks.lock().unwrap().insert(msg_id, tx);
+        let pending = Pending {
+            inner: self.inner.clone(),
+            msg_id,
+        };
+
+        let body = MessageBody {
+            msg_id: None,
+            in_reply_to: None,
+            payload,
+        };
+        let sent = self.write(dest.into(), msg_id, body);
+
+        // The clock starts when the request goes out, not when someone first polls for the reply
+        let deadline = Instant::now() + timeout;
+        async move {
+            let _pending = pending;
+            sent?;
+
+            let reply = match tokio::time::timeout_at(deadline, rx).await {
+                Ok(Ok(reply)) => reply,
+                Ok(Err(_)) => return Err(RpcError::new(ErrorCode::Crash, "runtime shut down before a reply arrived")),
+                Err(_) => return Err(RpcError::new(ErrorCode::Timeout, format!("no reply to msg {} within {:?}", msg_id, timeout))),
+            };
+
+            if reply.msg_type() == "error" {
+                return match reply.decode::<RuntimePayload>()?.body.payload {
+                    RuntimePayload::Error { code, text } => Err(RpcError::new(code, text)),
+                    other => Err(RpcError::new(ErrorCode::MalformedRequest, format!("unexpected reply {:?}", other))),
+                };
+            }
+            Ok(reply.decode::<R>()?.body)
+        }
+    }
+
+    // Hands out the next msg_id
+    fn next_msg_id(&self) -> u64 {
+        self.inner.msg_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    // Attaches a fresh msg_id to a body and queues it for delivery
+    fn send_body<P: Serialize>(&self, dest: String, body: MessageBody<P>) -> serde_json::Result<u64> {
+        let msg_id = self.next_msg_id();
+        self.write(dest, msg_id, body)?;
+        Ok(msg_id)
+    }
+
+    // Stamps a body with the given msg_id and queues it for STDOUT
+    fn write<P: Serialize>(&self, dest: String, msg_id: u64, mut body: MessageBody<P>) -> serde_json::Result<()> {
+        body.msg_id = Some(msg_id);
+
+        let out = Message {
+            src: self.id().to_string(),
+            dest,
+            body,
+        };
+
+        // Serialize to json and queue it for STDOUT, counting it by the payload's type on the way
+        let msg_type = type_of(&out.body.payload).unwrap_or_default();
+        let out_str = serde_json::to_string(&out)?;
+        {
+            let mut traffic = self.inner.traffic.lock().unwrap();
+            *traffic.sent.entry(msg_type).or_default() += 1;
+            traffic.bytes_sent += out_str.len() as u64 + 1;
+        }
+        debug!(dest = %out.dest, msg_id, "sending {}", out_str);
+        if self.inner.out.send(out_str).is_err() {
+            warn!("writer has gone away, dropping message");
+        }
+
+        Ok(())
+    }
+
+    // Runs a handler over STDIN/STDOUT until input runs out
+    pub async fn run<H: Handler>(handler: H) -> io::Result<()> {
+        Runtime::run_with(handler, LineTransport::stdio()).await
+    }
+
+    // Runs a handler over any transport until its input runs out
+    pub async fn run_with<H: Handler, T: Transport>(handler: H, transport: T) -> io::Result<()> {
+        let (inbound, outbound) = transport.split();
+
+        // Everything we send goes through a single writer, so envelopes never interleave
+        let (out_tx, out_rx) = mpsc::unbounded_channel();
+        let writer = tokio::spawn(write_lines(outbound, out_rx));
+
+        // Input is read on its own task, so waiting for it never blocks our timers
+        let (in_tx, mut in_rx) = mpsc::unbounded_channel();
+        tokio::spawn(read_lines(inbound, in_tx));
+
+        let mut node = Node::new(handler, out_tx);
+
+        // Loop over input until it runs out
+        loop {
+            let next = node.next_timer();
+            let sleep = tokio::time::sleep_until(next.unwrap_or_else(|| Instant::now() + Duration::from_secs(3600)));
+
+            tokio::select! {
+                line = in_rx.recv() => {
+                    // EOF means Maelstrom is done with us, so shut down cleanly
+                    let Some(buffer) = line else {
+                        break;
+                    };
+                    node.receive(&buffer);
+                },
+                _ = sleep, if next.is_some() => node.run_timers(),
+            }
+        }
+
+        // Let the writer drain whatever is still queued before we exit
+        drop(node);
+        writer.await?
+    }
+
+    // Decodes and dispatches one line of input. Nothing on a single line is worth dying over,
+    // so bad input is answered with an error where possible and otherwise logged and skipped.
+    pub fn receive<H: Handler>(&self, handler: &mut H, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        self.inner.traffic.lock().unwrap().bytes_received += line.len() as u64 + 1;
+
+        // Decode into json, and tag everything logged while serving it with where it came from
+        let result = match serde_json::from_str::<Message<Value>>(line) {
+            Ok(msg) => {
+                *self.inner.traffic.lock().unwrap().received.entry(msg.msg_type().to_string()).or_default() += 1;
+                let span = info_span!("message", src = %msg.src, msg_type = msg.msg_type(), msg_id = msg.body.msg_id);
+                let _entered = span.enter();
+                debug!("received {}", line);
+                self.dispatch(handler, msg)
+            },
+            Err(e) => {
+                debug!("received {}", line);
+                self.reject_line(line, e)
+            },
+        };
+        if let Err(e) = result {
+            error!("failed to respond to {}: {}", line, e);
+        }
+    }
+
+    // Answers a line that isn't a valid message, if we can tell who sent it and they expect a
+    // reply. A line that isn't even JSON, say one cut short, is searched for the fields instead.
+    fn reject_line(&self, line: &str, e: serde_json::Error) -> serde_json::Result<()> {
+        let value: Option<Value> = serde_json::from_str(line).ok();
+        let (src, msg_id) = match &value {
+            Some(value) => (
+                value.get("src").and_then(Value::as_str),
+                value.pointer("/body/msg_id").and_then(Value::as_u64),
+            ),
+            None => (salvage(line, "src"), salvage(line, "msg_id").and_then(|id| id.parse().ok())),
+        };
+        match src {
+            Some(src) => self.reject(src, msg_id, RpcError::new(ErrorCode::MalformedRequest, e.to_string())),
+            None => {
+                warn!("skipping malformed input: {}", e);
+                Ok(())
+            },
+        }
+    }
+
+    // Routes one inbound message: rpc replies and init are ours, everything else goes to the handler
+    fn dispatch<H: Handler>(&self, handler: &mut H, msg: Message<Value>) -> serde_json::Result<()> {
+        if let Some(in_reply_to) = msg.body.in_reply_to {
+            let callback = self.inner.callbacks.lock().unwrap().remove(&in_reply_to);
+            if let Some(callback) = callback {
+                // The caller may have stopped waiting, which is fine
+                let _ = callback.send(msg);
+                return Ok(());
+            }
+        }
+
+        // Hang on to who is asking, so we can still answer if the payload doesn't decode
+        let (src, msg_id) = (msg.src.clone(), msg.body.msg_id);
+        let malformed = |e: serde_json::Error| RpcError::new(ErrorCode::MalformedRequest, e.to_string());
+
+        if msg.msg_type() == "init" {
+            let msg: Message<RuntimePayload> = match msg.decode() {
+                Ok(msg) => msg,
+                Err(e) => return self.reject(&src, msg_id, malformed(e)),
+            };
+            if let RuntimePayload::Init { node_id, node_ids } = &msg.body.payload {
+                // Store our designated node ID as well as all the other nodes in the network
+                let info = NodeInfo {
+                    id: node_id.to_owned(),
+                    node_ids: node_ids.to_owned(),
+                };
+                // The handler set itself up the first time round, so doing it again could
+                // only replay its state on top of itself
+                if self.inner.node.set(info).is_err() {
+                    warn!("ignoring repeated init");
+                    return self.reply(&msg, RuntimePayload::InitOk);
+                }
+            }
+
+            return match handler.init(self) {
+                Ok(()) => self.reply(&msg, RuntimePayload::InitOk),
+                Err(e) => self.reply_error(&msg, e),
+            };
+        }
+
+        let msg: Message<H::Payload> = match msg.decode() {
+            Ok(msg) => msg,
+            Err(e) => return self.reject(&src, msg_id, malformed(e)),
+        };
+        let reply = match handler.handle(self, &msg) {
+            Ok(Some(reply)) => reply,
+            Ok(None) => return Ok(()),
+            Err(e) => return self.reply_error(&msg, e),
+        };
+
+        // Only requests carry a msg_id, and only requests need a response
+        if msg.body.msg_id.is_some() {
+            self.reply(&msg, reply)?;
+        }
+        Ok(())
+    }
+
+    // Tells the sender we couldn't serve their request, for handlers answering after the fact
+    pub fn reply_error<Q>(&self, request: &Message<Q>, e: RpcError) -> serde_json::Result<()> {
+        self.reject(&request.src, request.body.msg_id, e)
+    }
+
+    // Sends an error back to src, if they are waiting on an answer
+    fn reject(&self, src: &str, msg_id: Option<u64>, e: RpcError) -> serde_json::Result<()> {
+        warn!(%src, ?msg_id, code = u32::from(e.code), "rejecting message: {}", e.text);
+        if msg_id.is_none() {
+            return Ok(());
+        }
+
+        let body = MessageBody {
+            msg_id: None,
+            in_reply_to: msg_id,
+            payload: RuntimePayload::Error {
+                code: e.code,
+                text: e.text,
+            },
+        };
+        self.send_body(src.to_string(), body)?;
+        Ok(())
+    }
+}
+
+// Picks a string or integer field out of text that may not be valid JSON, as long as the
+// value itself made it through whole
+fn salvage<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let start = line.find(&format!("\"{}\"", key))? + key.len() + 2;
+    let rest = line[start..].trim_start().strip_prefix(':')?.trim_start();
+    if let Some(s) = rest.strip_prefix('"') {
+        return s.find('"').map(|end| &s[..end]);
+    }
+
+    // A number running to the end of the line may have been cut short
+    let end = rest.find(|c: char| !c.is_ascii_digit())?;
+    (end > 0).then(|| &rest[..end])
+}
+
+// An outstanding rpc call, whose callback is removed when this is dropped
+#[derive(Debug)]
+struct Pending {
+    inner: Arc<Inner>,
+    msg_id: u64,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.inner.callbacks.lock().unwrap().remove(&self.msg_id);
+    }
+}
+
+// A handler bound to a runtime and its timers, driven one input at a time. Runtime::run drives
+// one from STDIN; tests can drive them directly.
+pub struct Node<H: Handler> {
+    pub rt: Runtime,
+    pub handler: H,
+    timers: Vec<Timer<H>>,
+    due: Vec<Instant>,
+}
+
+impl<H: Handler> Node<H> {
+    // Binds a handler to a fresh runtime writing its output lines to `out`
+    pub fn new(handler: H, out: mpsc::UnboundedSender<String>) -> Node<H> {
+        // Timers first fire right away, then on their own interval
+        let timers = handler.timers();
+        let due = timers.iter().map(|_| Instant::now()).collect();
+        Node {
+            rt: Runtime::new(out),
+            handler,
+            timers,
+            due,
+        }
+    }
+
+    // Handles one line of input
+    pub fn receive(&mut self, line: &str) {
+        self.rt.receive(&mut self.handler, line);
+    }
+
+    // When the next timer is due, if there are any
+    pub fn next_timer(&self) -> Option<Instant> {
+        self.due.iter().min().copied()
+    }
+
+    // Runs every timer that is due by now
+    pub fn run_timers(&mut self) {
+        let now = Instant::now();
+        for (timer, due) in self.timers.iter().zip(self.due.iter_mut()) {
+            if *due > now {
+                continue;
+            }
+
+            // Background work shouldn't take the node down, so failures are only logged
+            if let Err(e) = (timer.run)(&mut self.handler, &self.rt) {
+                error!("background task failed: {}", e);
+            }
+            *due = now + timer.every;
+        }
+    }
+}
+
+// Reads envelopes and hands them to the dispatcher until the transport runs dry
+async fn read_lines(mut inbound: impl Inbound, tx: mpsc::UnboundedSender<String>) -> io::Result<()> {
+    while let Some(line) = inbound.recv().await? {
+        if tx.send(line).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Sends each outgoing envelope as soon as it is queued
+async fn write_lines(mut outbound: impl Outbound, mut rx: mpsc::UnboundedReceiver<String>) -> io::Result<()> {
+    while let Some(line) = rx.recv().await {
+        outbound.send(line).await?;
+    }
+    Ok(())
+}
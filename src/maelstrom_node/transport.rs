@@ -0,0 +1,158 @@
+use std::future::Future;
+use std::io;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Stdin, Stdout};
+use tokio::net::{tcp, TcpStream};
+use tokio::sync::mpsc;
+
+// Carries serialized envelopes, one JSON message each, in and out of a node. The two halves
+// run on separate tasks, so reading never waits on writing or the other way around.
+pub trait Transport {
+    type Inbound: Inbound;
+    type Outbound: Outbound;
+
+    fn split(self) -> (Self::Inbound, Self::Outbound);
+}
+
+// The receiving half of a transport
+pub trait Inbound: Send + 'static {
+    // The next envelope, or None once the other side is done with us
+    fn recv(&mut self) -> impl Future<Output = io::Result<Option<String>>> + Send;
+}
+
+// The sending half of a transport
+pub trait Outbound: Send + 'static {
+    fn send(&mut self, envelope: String) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+// Newline-delimited envelopes over any byte stream: STDIN/STDOUT under Maelstrom, or a socket
+pub struct LineTransport<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R, W> LineTransport<R, W> {
+    pub fn new(reader: R, writer: W) -> LineTransport<R, W> {
+        LineTransport { reader, writer }
+    }
+}
+
+impl LineTransport<Stdin, Stdout> {
+    // What Maelstrom speaks
+    pub fn stdio() -> LineTransport<Stdin, Stdout> {
+        LineTransport::new(tokio::io::stdin(), tokio::io::stdout())
+    }
+}
+
+impl LineTransport<tcp::OwnedReadHalf, tcp::OwnedWriteHalf> {
+    pub fn tcp(stream: TcpStream) -> LineTransport<tcp::OwnedReadHalf, tcp::OwnedWriteHalf> {
+        let (reader, writer) = stream.into_split();
+        LineTransport::new(reader, writer)
+    }
+}
+
+#[cfg(unix)]
+impl LineTransport<tokio::net::unix::OwnedReadHalf, tokio::net::unix::OwnedWriteHalf> {
+    pub fn unix(
+        stream: tokio::net::UnixStream,
+    ) -> LineTransport<tokio::net::unix::OwnedReadHalf, tokio::net::unix::OwnedWriteHalf> {
+        let (reader, writer) = stream.into_split();
+        LineTransport::new(reader, writer)
+    }
+}
+
+impl<R, W> Transport for LineTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    type Inbound = LineReader<R>;
+    type Outbound = LineWriter<W>;
+
+    fn split(self) -> (LineReader<R>, LineWriter<W>) {
+        let reader = LineReader {
+            reader: BufReader::new(self.reader),
+            buffer: Vec::new(),
+        };
+        (reader, LineWriter { writer: self.writer })
+    }
+}
+
+pub struct LineReader<R> {
+    reader: BufReader<R>,
+    buffer: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin + Send + 'static> Inbound for LineReader<R> {
+    // Invalid UTF-8 is replaced rather than treated as fatal, so it fails to parse like any
+    // other garbage line
+    async fn recv(&mut self) -> io::Result<Option<String>> {
+        self.buffer.clear();
+        if self.reader.read_until(b'\n', &mut self.buffer).await? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(&self.buffer).trim_end().to_string()))
+    }
+}
+
+pub struct LineWriter<W> {
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin + Send + 'static> Outbound for LineWriter<W> {
+    // Flushes every line, so the other side sees it right away
+    async fn send(&mut self, envelope: String) -> io::Result<()> {
+        self.writer.write_all(envelope.as_bytes()).await?;
+        self.writer.write_all(b"\n").await?;
+        self.writer.flush().await
+    }
+}
+
+// An in-memory transport, handy for tests. Create them in connected pairs with `channel`.
+pub struct ChannelTransport {
+    rx: mpsc::UnboundedReceiver<String>,
+    tx: mpsc::UnboundedSender<String>,
+}
+
+// Two connected transports: whatever one sends, the other receives
+pub fn channel() -> (ChannelTransport, ChannelTransport) {
+    let (a_tx, a_rx) = mpsc::unbounded_channel();
+    let (b_tx, b_rx) = mpsc::unbounded_channel();
+    let a = ChannelTransport { rx: a_rx, tx: b_tx };
+    let b = ChannelTransport { rx: b_rx, tx: a_tx };
+    (a, b)
+}
+
+impl ChannelTransport {
+    pub async fn recv(&mut self) -> Option<String> {
+        self.rx.recv().await
+    }
+
+    // Fails only once the other end has gone away
+    pub fn send(&self, envelope: impl Into<String>) -> io::Result<()> {
+        self.tx
+            .send(envelope.into())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "channel closed"))
+    }
+}
+
+impl Transport for ChannelTransport {
+    type Inbound = mpsc::UnboundedReceiver<String>;
+    type Outbound = mpsc::UnboundedSender<String>;
+
+    fn split(self) -> (Self::Inbound, Self::Outbound) {
+        (self.rx, self.tx)
+    }
+}
+
+impl Inbound for mpsc::UnboundedReceiver<String> {
+    async fn recv(&mut self) -> io::Result<Option<String>> {
+        Ok(mpsc::UnboundedReceiver::recv(self).await)
+    }
+}
+
+impl Outbound for mpsc::UnboundedSender<String> {
+    async fn send(&mut self, envelope: String) -> io::Result<()> {
+        mpsc::UnboundedSender::send(self, envelope).map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "channel closed"))
+    }
+}
@@ -0,0 +1,238 @@
+// Finds the "type" field of a payload we're about to send, without serializing the rest of
+// it. Payloads are structs, tagged enums or JSON objects, all of which serde hands to a
+// serializer one field at a time, so we look at the field names and serialize only the value
+// of the one we want. Anything else has no type.
+
+use std::fmt;
+
+use serde::ser::{self, Impossible, Serialize, SerializeMap, SerializeStruct, Serializer};
+use serde_json::Value;
+
+// The "type" field of a payload, if it has one that's a string
+pub(crate) fn type_of<P: Serialize + ?Sized>(payload: &P) -> Option<String> {
+    payload.serialize(TypeOf).ok().flatten()
+}
+
+// Stops the search once we know there's no type to find
+#[derive(Debug)]
+struct NoType;
+
+impl fmt::Display for NoType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("payload has no type")
+    }
+}
+
+impl std::error::Error for NoType {}
+
+impl ser::Error for NoType {
+    fn custom<T: fmt::Display>(_msg: T) -> NoType {
+        NoType
+    }
+}
+
+// The value of a field named "type", if it's a string
+fn type_value<T: Serialize + ?Sized>(value: &T) -> Option<String> {
+    match serde_json::to_value(value) {
+        Ok(Value::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+struct TypeOf;
+
+impl Serializer for TypeOf {
+    type Ok = Option<String>;
+    type Error = NoType;
+    type SerializeSeq = Impossible<Option<String>, NoType>;
+    type SerializeTuple = Impossible<Option<String>, NoType>;
+    type SerializeTupleStruct = Impossible<Option<String>, NoType>;
+    type SerializeTupleVariant = Impossible<Option<String>, NoType>;
+    type SerializeMap = Fields;
+    type SerializeStruct = Fields;
+    type SerializeStructVariant = Impossible<Option<String>, NoType>;
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Fields, NoType> {
+        Ok(Fields::default())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Fields, NoType> {
+        Ok(Fields::default())
+    }
+
+    // Wrappers around a payload have the payload's type
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Option<String>, NoType> {
+        value.serialize(self)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Option<String>, NoType> {
+        value.serialize(self)
+    }
+
+    // Nothing below is an object, so none of it has a type
+    fn serialize_bool(self, _v: bool) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_none(self) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_unit(self) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, _variant: &'static str) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Option<String>, NoType> {
+        Ok(None)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, NoType> {
+        Err(NoType)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, NoType> {
+        Err(NoType)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, NoType> {
+        Err(NoType)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, NoType> {
+        Err(NoType)
+    }
+
+    // An externally tagged variant nests its fields a level down, out of our reach
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, NoType> {
+        Err(NoType)
+    }
+}
+
+// Walks the fields of an object, keeping the type once it goes past
+#[derive(Default)]
+struct Fields {
+    // Whether the key just seen was "type", for maps, which hand over keys and values apart
+    at_type: bool,
+    found: Option<String>,
+}
+
+impl Fields {
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) {
+        if key == "type" && self.found.is_none() {
+            self.found = type_value(value);
+        }
+    }
+}
+
+impl SerializeStruct for Fields {
+    type Ok = Option<String>;
+    type Error = NoType;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), NoType> {
+        self.field(key, value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Option<String>, NoType> {
+        Ok(self.found)
+    }
+}
+
+impl SerializeMap for Fields {
+    type Ok = Option<String>;
+    type Error = NoType;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), NoType> {
+        self.at_type = type_value(key).is_some_and(|k| k == "type");
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NoType> {
+        if std::mem::take(&mut self.at_type) {
+            self.field("type", value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Option<String>, NoType> {
+        Ok(self.found)
+    }
+}
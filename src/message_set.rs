@@ -0,0 +1,274 @@
+// The set of values a broadcast node holds. Gossip carries values rather than pointing into
+// the set, so all the set has to do is spot duplicates, list and hash everything it holds,
+// and list what arrived since a version for incremental reads. One backend holds any JSON
+// value and remembers all of them in arrival order. The other holds only non-negative
+// integers, as runs of consecutive values plus a bounded tail of recent arrivals, so a dense
+// range costs the same however far it grows.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::str::FromStr;
+
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::maelstrom_node::{ErrorCode, RpcError};
+use crate::rng::hash_bytes;
+
+// How many of the most recent arrivals the interval backend remembers in order
+pub const RECENT_ARRIVALS: usize = 1024;
+
+// Which backend to keep values in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Storage {
+    // Any JSON value, told apart by its canonical encoding
+    #[default]
+    Json,
+
+    // Non-negative integers only, kept as runs of consecutive values. Only the most recent
+    // arrivals are remembered in order, so an incremental read from further back gets
+    // everything.
+    Intervals,
+}
+
+impl FromStr for Storage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Storage, String> {
+        match s {
+            "json" => Ok(Storage::Json),
+            "intervals" | "runs" => Ok(Storage::Intervals),
+            _ => Err(format!("unknown storage: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MessageSet {
+    Json {
+        log: Vec<Value>,
+
+        // Each value's position, keyed by its canonical encoding, and its hash in log order
+        index: HashMap<String, usize>,
+        hashes: Vec<u64>,
+    },
+    Intervals {
+        // Runs of held values, from first to last inclusive, keyed by their first
+        runs: BTreeMap<u64, u64>,
+
+        // How many values we hold, which is also how many have ever arrived
+        len: usize,
+
+        // The last RECENT_ARRIVALS values to arrive, oldest first
+        recent: VecDeque<u64>,
+    },
+}
+
+// Values listed for a read. The interval backend hands over its runs, which are only spelled
+// out one value at a time as the listing is written.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MessageList {
+    Values(Vec<Value>),
+    Runs(Vec<(u64, u64)>),
+}
+
+impl MessageSet {
+    pub fn new(storage: Storage) -> MessageSet {
+        match storage {
+            Storage::Json => MessageSet::Json {
+                log: Vec::new(),
+                index: HashMap::new(),
+                hashes: Vec::new(),
+            },
+            Storage::Intervals => MessageSet::Intervals {
+                runs: BTreeMap::new(),
+                len: 0,
+                recent: VecDeque::new(),
+            },
+        }
+    }
+
+    // Whether this backend can hold a value at all
+    pub fn check(&self, value: &Value) -> Result<(), RpcError> {
+        match self {
+            MessageSet::Intervals { .. } if value.as_u64().is_none() => Err(RpcError::new(
+                ErrorCode::MalformedRequest,
+                format!("{} is not a non-negative integer, which is all this node stores", value),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Adds a value unless we already hold it, returning whether it was new. Values the
+    // backend can't hold are never new.
+    pub fn insert(&mut self, value: Value) -> bool {
+        self.add(value).is_some()
+    }
+
+    // Like insert, but returns the hash of a value that was new, for the anti-entropy digest
+    pub fn add(&mut self, value: Value) -> Option<u64> {
+        match self {
+            MessageSet::Json { log, index, hashes } => {
+                // Without serde_json's preserve_order, objects serialize with sorted keys, so
+                // equal values always encode the same way
+                let key = value.to_string();
+                if index.contains_key(&key) {
+                    return None;
+                }
+                let hash = hash_bytes(key.as_bytes());
+                hashes.push(hash);
+                index.insert(key, log.len());
+                log.push(value);
+                Some(hash)
+            },
+            MessageSet::Intervals { runs, len, recent } => {
+                let v = value.as_u64()?;
+                let before = runs.range(..=v).next_back().map(|(first, last)| (*first, *last));
+                if matches!(before, Some((_, last)) if last >= v) {
+                    return None;
+                }
+
+                // Join the run ending just below, the run starting just above, or both
+                let first = match before {
+                    Some((first, last)) if last + 1 == v => first,
+                    _ => v,
+                };
+                let last = match v.checked_add(1).and_then(|next| runs.remove(&next)) {
+                    Some(last) => last,
+                    None => v,
+                };
+                runs.insert(first, last);
+
+                *len += 1;
+                if recent.len() == RECENT_ARRIVALS {
+                    recent.pop_front();
+                }
+                recent.push_back(v);
+                Some(hash_u64(v))
+            },
+        }
+    }
+
+    // How many values we hold
+    pub fn len(&self) -> usize {
+        match self {
+            MessageSet::Json { log, .. } => log.len(),
+            MessageSet::Intervals { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The hash of every value we hold, for anti-entropy. Both backends hash the canonical
+    // encoding, so nodes agree on digests whichever backend they run.
+    pub fn hashes(&self) -> Box<dyn Iterator<Item = u64> + '_> {
+        match self {
+            MessageSet::Json { hashes, .. } => Box::new(hashes.iter().copied()),
+            MessageSet::Intervals { runs, .. } => Box::new(integers(runs).map(hash_u64)),
+        }
+    }
+
+    // Every value we hold whose hash passes a test
+    pub fn matching(&self, keep: impl Fn(u64) -> bool) -> Vec<Value> {
+        match self {
+            MessageSet::Json { log, hashes, .. } => log
+                .iter()
+                .zip(hashes)
+                .filter(|(_, h)| keep(**h))
+                .map(|(v, _)| v.clone())
+                .collect(),
+            MessageSet::Intervals { runs, .. } => integers(runs).filter(|v| keep(hash_u64(*v))).map(Value::from).collect(),
+        }
+    }
+
+    // Every value that arrived after the given version, which is a count of values held, in
+    // the order they arrived. The interval backend only remembers the most recent arrivals,
+    // so from further back it lists everything instead.
+    pub fn since(&self, version: usize) -> MessageList {
+        let version = version.min(self.len());
+        match self {
+            MessageSet::Json { log, .. } => MessageList::Values(log[version..].to_vec()),
+            MessageSet::Intervals { len, recent, .. } => match version.checked_sub(len - recent.len()) {
+                Some(skip) => MessageList::Values(recent.iter().skip(skip).map(|v| Value::from(*v)).collect()),
+                None => self.all(),
+            },
+        }
+    }
+
+    // Every value, for a full read. The interval backend lists them in ascending order,
+    // straight from its runs.
+    pub fn all(&self) -> MessageList {
+        match self {
+            MessageSet::Json { log, .. } => MessageList::Values(log.clone()),
+            MessageSet::Intervals { runs, .. } => MessageList::Runs(runs.iter().map(|(first, last)| (*first, *last)).collect()),
+        }
+    }
+}
+
+impl MessageList {
+    pub fn len(&self) -> usize {
+        match self {
+            MessageList::Values(values) => values.len(),
+            MessageList::Runs(runs) => runs.iter().map(|(first, last)| ((last - first) as usize).saturating_add(1)).sum(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Spells the listing out as values
+    pub fn into_values(self) -> Vec<Value> {
+        match self {
+            MessageList::Values(values) => values,
+            MessageList::Runs(runs) => runs.into_iter().flat_map(|(first, last)| first..=last).map(Value::from).collect(),
+        }
+    }
+}
+
+// Written out as a plain array, so readers can't tell which backend listed it
+impl Serialize for MessageList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MessageList::Values(values) => values.serialize(serializer),
+            MessageList::Runs(runs) => {
+                let mut seq = serializer.serialize_seq(Some(self.len()))?;
+                for (first, last) in runs {
+                    for v in *first..=*last {
+                        seq.serialize_element(&v)?;
+                    }
+                }
+                seq.end()
+            },
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MessageList, D::Error> {
+        Vec::deserialize(deserializer).map(MessageList::Values)
+    }
+}
+
+// Every integer in a set of runs, in ascending order
+fn integers(runs: &BTreeMap<u64, u64>) -> impl Iterator<Item = u64> + '_ {
+    runs.iter().flat_map(|(first, last)| *first..=*last)
+}
+
+// Hashes an integer's decimal encoding, the same bytes the json backend hashes for it, without
+// building a string
+fn hash_u64(mut v: u64) -> u64 {
+    let mut digits = [0u8; 20];
+    let mut start = digits.len();
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (v % 10) as u8;
+        v /= 10;
+        if v == 0 {
+            break;
+        }
+    }
+    hash_bytes(&digits[start..])
+}
@@ -0,0 +1,50 @@
+// A small seeded random number generator, so every node can derive the same "random" choices
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    // Next pseudo-random 64-bit value
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        mix(self.state)
+    }
+
+    // A value in 0..n, n must be non-zero
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    // Fisher-Yates shuffle in place
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+// A fast, well-mixed 64-bit hash step (from SplitMix64)
+pub fn splitmix64(x: u64) -> u64 {
+    mix(x.wrapping_add(0x9e3779b97f4a7c15))
+}
+
+// Hashes a byte string to 64 well-mixed bits, the same way in every process
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    bytes.chunks(8).fold(splitmix64(bytes.len() as u64), |h, chunk| {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        splitmix64(h ^ u64::from_le_bytes(word))
+    })
+}
+
+// The SplitMix64 finalizer
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,117 @@
+// An optional on-disk copy of a node's message set: an append-only log of JSON values, one
+// per line, folded into a snapshot every so often, so a restarted node comes back with
+// everything it had.
+
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug)]
+pub struct Store {
+    log_path: PathBuf,
+    snapshot_path: PathBuf,
+    log: File,
+
+    // Values appended since the last snapshot
+    pending: usize,
+}
+
+impl Store {
+    // Opens (or creates) the files for a node in dir, returning the store along with every value
+    // recovered from it, in the order they were first written
+    pub fn open(dir: &Path, node_id: &str) -> io::Result<(Store, Vec<Value>)> {
+        fs::create_dir_all(dir)?;
+        let log_path = dir.join(format!("{}.log", node_id));
+        let snapshot_path = dir.join(format!("{}.snapshot", node_id));
+
+        let mut values = Vec::new();
+        if snapshot_path.exists() {
+            values = serde_json::from_slice(&fs::read(&snapshot_path)?)?;
+        }
+
+        let mut pending = 0;
+        let mut good = 0;
+        if log_path.exists() {
+            let mut reader = BufReader::new(File::open(&log_path)?);
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let n = reader.read_line(&mut line)?;
+
+                // A crash can leave half a line at the end, which we simply never acknowledged
+                if !line.ends_with('\n') {
+                    break;
+                }
+                match serde_json::from_str(line.trim()) {
+                    Ok(value) => {
+                        values.push(value);
+                        pending += 1;
+                        good += n as u64;
+                    },
+                    Err(_) => break,
+                }
+            }
+        }
+
+        // Snapshots and logs can overlap if we crashed mid-compaction
+        let mut seen = HashSet::new();
+        values.retain(|v: &Value| seen.insert(v.to_string()));
+
+        // Cut off anything torn so new appends don't get glued onto it
+        let log = OpenOptions::new().create(true).append(true).open(&log_path)?;
+        log.set_len(good)?;
+        let store = Store {
+            log_path,
+            snapshot_path,
+            log,
+            pending,
+        };
+        Ok((store, values))
+    }
+
+    // Durably appends new values to the log
+    pub fn append(&mut self, values: &[Value]) -> io::Result<()> {
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let mut buffer = String::new();
+        for v in values {
+            buffer.push_str(&v.to_string());
+            buffer.push('\n');
+        }
+        self.log.write_all(buffer.as_bytes())?;
+        self.log.sync_data()?;
+        self.pending += values.len();
+        Ok(())
+    }
+
+    // Whether anything has been appended since the last snapshot, and so whether compact has
+    // anything to do
+    pub fn is_dirty(&self) -> bool {
+        self.pending > 0
+    }
+
+    // Replaces the snapshot with the full set of values and starts a fresh log. The snapshot
+    // is swapped in with a rename, so a crash at any point leaves a readable pair of files.
+    pub fn compact<T: Serialize + ?Sized>(&mut self, all: &T) -> io::Result<()> {
+        if self.pending == 0 {
+            return Ok(());
+        }
+
+        let tmp = self.snapshot_path.with_extension("snapshot.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(all)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.snapshot_path)?;
+
+        self.log = File::create(&self.log_path)?;
+        self.log.sync_all()?;
+        self.pending = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::str::FromStr;
+
+use tracing::info;
+
+use crate::maelstrom_node::{ErrorCode, RpcError};
+use crate::rng::Rng;
+
+// Seed shared by every node so they all build the same random graph
+const RANDOM_SEED: u64 = 0x6d61656c;
+
+// How a node picks its gossip neighbors
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+    // Use whatever Maelstrom suggests in the topology message
+    #[default]
+    Maelstrom,
+
+    // A breadth-first spanning tree of Maelstrom's suggested graph, rooted at the first node
+    Spanning,
+
+    // The first node is a hub and everyone else talks only to it
+    Star,
+
+    // A k-ary tree laid over the node list in order
+    Tree(usize),
+
+    // A random graph where every node has roughly k neighbors
+    Random(usize),
+
+    // A ring where every node also links to peers 2, 4, 8, ... positions away, up to this many chords
+    RingChords(usize),
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    // Parses names like "star", "tree:4", "random:3" or "ring-chords:2"
+    fn from_str(s: &str) -> Result<Topology, String> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg.parse::<usize>().map_err(|e| e.to_string())?)),
+            None => (s, None),
+        };
+
+        match name {
+            "maelstrom" | "grid" => Ok(Topology::Maelstrom),
+            "spanning" => Ok(Topology::Spanning),
+            "star" | "hub" => Ok(Topology::Star),
+            "tree" => Ok(Topology::Tree(arg.unwrap_or(2).max(1))),
+            "random" => Ok(Topology::Random(arg.unwrap_or(3).max(1))),
+            "ring-chords" | "ring" => Ok(Topology::RingChords(arg.unwrap_or(2))),
+            _ => Err(format!("unknown topology: {}", s)),
+        }
+    }
+}
+
+impl Topology {
+    // Works out who `id` should gossip with. Every strategy is symmetric, so if a is b's
+    // neighbor then b is a's, and values can flow both ways. Returns None when `id` doesn't
+    // appear in the graph we'd build from.
+    pub fn neighbors(&self, id: &str, node_ids: &[String], suggested: &HashMap<String, Vec<String>>) -> Option<Vec<String>> {
+        let Some(me) = node_ids.iter().position(|n| n == id) else {
+            return suggested.get(id).cloned();
+        };
+        let n = node_ids.len();
+
+        let indexes: BTreeSet<usize> = match self {
+            Topology::Maelstrom => return suggested.get(id).cloned(),
+            Topology::Spanning => return Some(spanning_tree(id, node_ids, suggested)),
+            Topology::Star => {
+                if me == 0 {
+                    (1..n).collect()
+                } else {
+                    BTreeSet::from([0])
+                }
+            },
+            Topology::Tree(k) => {
+                let mut set: BTreeSet<usize> = (k * me + 1..=k * me + k).filter(|c| *c < n).collect();
+                if me > 0 {
+                    set.insert((me - 1) / k);
+                }
+                set
+            },
+            Topology::Random(k) => random_graph(n, *k).remove(me),
+            Topology::RingChords(chords) => {
+                let mut set = BTreeSet::new();
+                for j in 0..=*chords {
+                    let step = (1usize << j.min(32)) % n;
+                    set.insert((me + step) % n);
+                    set.insert((me + n - step) % n);
+                }
+                set
+            },
+        };
+
+        let neighbors = indexes
+            .into_iter()
+            .filter(|i| *i != me)
+            .map(|i| node_ids[i].clone())
+            .collect();
+        Some(neighbors)
+    }
+
+    // Who `id` gossips with before any topology message arrives, which for workloads other
+    // than broadcast is never. Maelstrom's suggestion is taken to be everyone-to-everyone.
+    pub fn default_neighbors(&self, id: &str, node_ids: &[String]) -> Vec<String> {
+        let everyone: HashMap<String, Vec<String>> = node_ids
+            .iter()
+            .map(|n| (n.clone(), node_ids.iter().filter(|m| *m != n).cloned().collect()))
+            .collect();
+        let neighbors = self.neighbors(id, node_ids, &everyone).unwrap_or_default();
+        info!("neighbors default to {:?}", neighbors);
+        neighbors
+    }
+
+    // Who `id` gossips with once Maelstrom has suggested a graph, for a topology message
+    pub fn suggested_neighbors(&self, id: &str, node_ids: &[String], suggested: &HashMap<String, Vec<String>>) -> Result<Vec<String>, RpcError> {
+        let neighbors = self
+            .neighbors(id, node_ids, suggested)
+            .ok_or_else(|| RpcError::new(ErrorCode::MalformedRequest, format!("topology has no entry for {}", id)))?;
+        info!("neighbors set to {:?}", neighbors);
+        Ok(neighbors)
+    }
+}
+
+// Breadth-first search over the suggested graph, keeping only tree edges touching `id`
+fn spanning_tree(id: &str, node_ids: &[String], suggested: &HashMap<String, Vec<String>>) -> Vec<String> {
+    let Some(root) = node_ids.first() else {
+        return Vec::new();
+    };
+
+    let mut parent: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from([root.as_str()]);
+    parent.insert(root, root);
+    while let Some(node) = queue.pop_front() {
+        for next in suggested.get(node).into_iter().flatten() {
+            if !parent.contains_key(next.as_str()) {
+                parent.insert(next, node);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let mut neighbors: Vec<String> = parent
+        .iter()
+        .filter(|(child, p)| **p == id && **child != id)
+        .map(|(child, _)| child.to_string())
+        .collect();
+    if let Some(p) = parent.get(id) {
+        if *p != id {
+            neighbors.push(p.to_string());
+        }
+    }
+    neighbors.sort();
+    neighbors
+}
+
+// Overlays enough random Hamiltonian cycles to give each node about k neighbors
+fn random_graph(n: usize, k: usize) -> Vec<BTreeSet<usize>> {
+    let mut rng = Rng::new(RANDOM_SEED);
+    let mut graph = vec![BTreeSet::new(); n];
+    if n < 2 {
+        return graph;
+    }
+
+    for _ in 0..k.div_ceil(2) {
+        let mut order: Vec<usize> = (0..n).collect();
+        rng.shuffle(&mut order);
+        for i in 0..n {
+            let (a, b) = (order[i], order[(i + 1) % n]);
+            if a != b {
+                graph[a].insert(b);
+                graph[b].insert(a);
+            }
+        }
+    }
+    graph
+}
@@ -0,0 +1,180 @@
+// A totally available transactional register store. Each transaction runs against our local
+// map in one go, so nothing else ever sees it half done, and its writes are then published
+// through a broadcast layer as a single value holding the last write per key. Other nodes
+// apply that value all at once, so they never see an intermediate or partial transaction
+// either, which makes the store read committed. Every transaction is stamped with a Lamport
+// clock and our node ID, and each node keeps the write with the highest stamp per key, so
+// replicas converge whatever order the writes reach them in.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::broadcast::Broadcast;
+use crate::config::Config;
+use crate::maelstrom_node::{ErrorCode, Handler, Message, RpcError, Runtime, Timer};
+use crate::message_set::Storage;
+use crate::workloads::forward;
+
+// One micro-operation: ["r", key, null] or ["w", key, value]. Reads come back with the value
+// filled in.
+pub type Op = (String, u64, Option<u64>);
+
+// The client-facing messages of the txn workload, tagged by their "type" field
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Payload {
+    Txn {
+        txn: Vec<Op>,
+    },
+    TxnOk {
+        txn: Vec<Op>,
+    },
+}
+
+// When a write happened: a Lamport clock, with the writer's node ID to break ties
+type Stamp = (u64, String);
+
+// A transaction's writes as published over broadcast
+#[derive(Serialize, Deserialize, Debug)]
+struct Writes {
+    clock: u64,
+    node: String,
+    writes: Vec<(u64, Option<u64>)>,
+}
+
+#[derive(Debug)]
+pub struct Txn {
+    broadcast: Broadcast,
+
+    // Each key's value, and the stamp of the write that set it
+    registers: BTreeMap<u64, (Stamp, Option<u64>)>,
+    clock: u64,
+
+    // How far into the broadcast log we have applied
+    merged: usize,
+}
+
+impl Txn {
+    pub fn new(config: Config) -> Txn {
+        Txn {
+            // Our values are objects, whatever STORAGE asks client broadcasts to be kept as
+            broadcast: Broadcast::new(Config {
+                storage: Storage::Json,
+                ..config
+            }),
+            registers: BTreeMap::new(),
+            clock: 0,
+            merged: 0,
+        }
+    }
+
+    // Sets a register, unless it already holds a later write. Writes from one transaction
+    // share a stamp, so among those the last one applied wins.
+    fn apply(&mut self, key: u64, stamp: &Stamp, value: Option<u64>) {
+        let register = self.registers.entry(key).or_insert(((0, String::new()), None));
+        if *stamp >= register.0 {
+            *register = (stamp.clone(), value);
+        }
+    }
+
+    // Applies any writes that have arrived since we last looked
+    fn merge(&mut self) {
+        let arrived: Vec<Writes> = self
+            .broadcast
+            .values_since(self.merged)
+            .into_values()
+            .into_iter()
+            .filter_map(|v| serde_json::from_value(v).ok())
+            .collect();
+        self.merged = self.broadcast.version();
+
+        for Writes { clock, node, writes } in arrived {
+            self.clock = self.clock.max(clock);
+            let stamp = (clock, node);
+            for (key, value) in writes {
+                self.apply(key, &stamp, value);
+            }
+        }
+    }
+
+    // Runs a transaction against our registers and publishes what it wrote
+    fn execute(&mut self, rt: &Runtime, txn: &[Op]) -> Result<Vec<Op>, RpcError> {
+        self.merge();
+        self.clock += 1;
+        let stamp = (self.clock, rt.id().to_string());
+
+        let mut results = Vec::with_capacity(txn.len());
+        let mut writes = Vec::new();
+        for (f, key, value) in txn {
+            match f.as_str() {
+                "r" => {
+                    let read = self.registers.get(key).and_then(|(_, v)| *v);
+                    results.push((f.clone(), *key, read));
+                },
+                "w" => {
+                    self.apply(*key, &stamp, *value);
+                    writes.push((*key, *value));
+                    results.push((f.clone(), *key, *value));
+                },
+                _ => return Err(RpcError::new(ErrorCode::MalformedRequest, format!("unknown micro-op {}", f))),
+            }
+        }
+
+        // Later writes to a key within the transaction replace earlier ones
+        let writes: Vec<(u64, Option<u64>)> = writes.into_iter().collect::<BTreeMap<_, _>>().into_iter().collect();
+        if !writes.is_empty() {
+            let (clock, node) = stamp;
+            self.broadcast.publish(rt, json!(Writes { clock, node, writes }))?;
+            self.merged = self.broadcast.version();
+        }
+        Ok(results)
+    }
+}
+
+impl Handler for Txn {
+    type Payload = Value;
+
+    // The broadcast layer picks its own neighbors, since Maelstrom never sends this workload a
+    // topology. Writes recovered from disk are applied before we serve anything.
+    fn init(&mut self, rt: &Runtime) -> Result<(), RpcError> {
+        self.broadcast.init(rt)?;
+        self.merge();
+        Ok(())
+    }
+
+    fn handle(&mut self, rt: &Runtime, msg: &Message<Value>) -> Result<Option<Value>, RpcError> {
+        match msg.msg_type() {
+            "txn" => {
+                let msg: Message<Payload> = msg
+                    .clone()
+                    .decode()
+                    .map_err(|e| RpcError::new(ErrorCode::MalformedRequest, e.to_string()))?;
+                let Payload::Txn { txn } = msg.body.payload else {
+                    return Err(RpcError::new(ErrorCode::NotSupported, "unsupported message type"));
+                };
+                let txn = self.execute(rt, &txn)?;
+                Ok(Some(serde_json::to_value(Payload::TxnOk { txn })?))
+            },
+
+            // Our values are writes, so clients don't get to broadcast or read them directly
+            "broadcast" | "read" | "txn_ok" => Err(RpcError::new(ErrorCode::NotSupported, "unsupported message type")),
+
+            // Everything else is the broadcast layer talking to itself
+            _ => {
+                let reply = forward(&mut self.broadcast, rt, msg)?;
+                self.merge();
+                Ok(reply)
+            },
+        }
+    }
+
+    fn timers(&self) -> Vec<Timer<Txn>> {
+        self.broadcast
+            .timers()
+            .into_iter()
+            .map(|t| t.lift(|txn: &mut Txn| Some(&mut txn.broadcast)))
+            .collect()
+    }
+}
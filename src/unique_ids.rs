@@ -0,0 +1,58 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::maelstrom_node::{ErrorCode, Handler, Message, RpcError, Runtime};
+
+// Every kind of message the unique-ids workload sends or receives, tagged by its "type" field
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Payload {
+    Generate,
+    GenerateOk {
+        id: String,
+    },
+
+    // Anything with a type we don't recognize
+    #[serde(other)]
+    Unknown,
+}
+
+// The unique-ids workload. An ID is our node ID, the time we started and a counter, so IDs
+// never collide across nodes, nor across restarts of the same node, without any coordination.
+#[derive(Debug, Default)]
+pub struct UniqueIds {
+    // Milliseconds since the Unix epoch when init ran
+    epoch: u128,
+    counter: u64,
+}
+
+impl UniqueIds {
+    pub fn new() -> UniqueIds {
+        UniqueIds::default()
+    }
+}
+
+impl Handler for UniqueIds {
+    type Payload = Payload;
+
+    fn init(&mut self, _rt: &Runtime) -> Result<(), RpcError> {
+        self.epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| RpcError::new(ErrorCode::Crash, e.to_string()))?
+            .as_millis();
+        Ok(())
+    }
+
+    fn handle(&mut self, rt: &Runtime, msg: &Message<Payload>) -> Result<Option<Payload>, RpcError> {
+        match &msg.body.payload {
+            Payload::Generate => {
+                self.counter += 1;
+                Ok(Some(Payload::GenerateOk {
+                    id: format!("{}-{}-{}", rt.id(), self.epoch, self.counter),
+                }))
+            },
+            Payload::GenerateOk { .. } | Payload::Unknown => Err(RpcError::new(ErrorCode::NotSupported, "unsupported message type")),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// Several workloads served by one node. Each message goes to whichever workload owns its
+// type, so a single binary can answer any of the challenges.
+
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::broadcast::Broadcast;
+use crate::config::Config;
+use crate::echo::Echo;
+use crate::g_counter::GCounter;
+use crate::kafka::Kafka;
+use crate::maelstrom_node::{ErrorCode, Handler, Message, RpcError, Runtime, Timer};
+use crate::txn::Txn;
+use crate::unique_ids::UniqueIds;
+
+// Which workloads a node serves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WorkloadSet {
+    pub echo: bool,
+    pub unique_ids: bool,
+    pub broadcast: bool,
+    pub g_counter: bool,
+    pub kafka: bool,
+    pub txn: bool,
+}
+
+impl Default for WorkloadSet {
+    fn default() -> WorkloadSet {
+        WorkloadSet {
+            echo: true,
+            unique_ids: true,
+            broadcast: true,
+            g_counter: false,
+            kafka: true,
+            txn: false,
+        }
+    }
+}
+
+impl FromStr for WorkloadSet {
+    type Err = String;
+
+    // Parses a comma-separated list like "echo,unique-ids", or "all" for every workload
+    // that can share a node with the others
+    fn from_str(s: &str) -> Result<WorkloadSet, String> {
+        let mut set = WorkloadSet {
+            echo: false,
+            unique_ids: false,
+            broadcast: false,
+            g_counter: false,
+            kafka: false,
+            txn: false,
+        };
+        for name in s.split(',').map(str::trim) {
+            match name {
+                "all" => set = WorkloadSet::default(),
+                "echo" => set.echo = true,
+                "unique-ids" | "generate" => set.unique_ids = true,
+                "broadcast" => set.broadcast = true,
+                "g-counter" | "counter" => set.g_counter = true,
+                "kafka" => set.kafka = true,
+                "txn" | "txn-rw-register" => set.txn = true,
+                _ => return Err(format!("unknown workload: {}", name)),
+            }
+        }
+
+        // These all answer read and topology, so only one of them can have a node
+        if [set.broadcast, set.g_counter, set.txn].iter().filter(|on| **on).count() > 1 {
+            return Err("only one of broadcast, g-counter and txn can be served at a time".to_string());
+        }
+        Ok(set)
+    }
+}
+
+#[derive(Debug)]
+pub struct Workloads {
+    echo: Option<Echo>,
+    unique_ids: Option<UniqueIds>,
+    broadcast: Option<Broadcast>,
+    g_counter: Option<GCounter>,
+    kafka: Option<Kafka>,
+    txn: Option<Txn>,
+}
+
+impl Workloads {
+    // Sets up the workloads the config asks for
+    pub fn new(config: Config) -> Workloads {
+        let set = config.workloads;
+        Workloads {
+            echo: set.echo.then(Echo::default),
+            unique_ids: set.unique_ids.then(UniqueIds::new),
+            broadcast: set.broadcast.then(|| Broadcast::new(config.clone())),
+            g_counter: set.g_counter.then(|| GCounter::new(config.clone())),
+            kafka: set.kafka.then(Kafka::new),
+            txn: set.txn.then(|| Txn::new(config)),
+        }
+    }
+}
+
+impl Handler for Workloads {
+    type Payload = Value;
+
+    fn init(&mut self, rt: &Runtime) -> Result<(), RpcError> {
+        if let Some(unique_ids) = &mut self.unique_ids {
+            unique_ids.init(rt)?;
+        }
+        if let Some(broadcast) = &mut self.broadcast {
+            broadcast.init(rt)?;
+        }
+        if let Some(g_counter) = &mut self.g_counter {
+            g_counter.init(rt)?;
+        }
+        if let Some(txn) = &mut self.txn {
+            txn.init(rt)?;
+        }
+        Ok(())
+    }
+
+    // Whichever of broadcast, g-counter or txn we run also owns gossip between nodes and any
+    // errors peers send back, so it gets everything that isn't obviously someone else's.
+    // Types nobody here serves are refused.
+    fn handle(&mut self, rt: &Runtime, msg: &Message<Value>) -> Result<Option<Value>, RpcError> {
+        let served = match msg.msg_type() {
+            "echo" => self.echo.as_mut().map(|h| forward(h, rt, msg)),
+            "generate" => self.unique_ids.as_mut().map(|h| forward(h, rt, msg)),
+            "send" | "poll" | "commit_offsets" | "list_committed_offsets" => self.kafka.as_mut().map(|h| forward(h, rt, msg)),
+            _ => {
+                if let Some(h) = &mut self.broadcast {
+                    Some(forward(h, rt, msg))
+                } else if let Some(h) = &mut self.g_counter {
+                    Some(forward(h, rt, msg))
+                } else {
+                    self.txn.as_mut().map(|h| forward(h, rt, msg))
+                }
+            },
+        };
+        served.unwrap_or_else(|| Err(RpcError::new(ErrorCode::NotSupported, format!("no workload serves {}", msg.msg_type()))))
+    }
+
+    fn timers(&self) -> Vec<Timer<Workloads>> {
+        let mut timers = Vec::new();
+        if let Some(broadcast) = &self.broadcast {
+            timers.extend(broadcast.timers().into_iter().map(|t| t.lift(|w: &mut Workloads| w.broadcast.as_mut())));
+        }
+        if let Some(g_counter) = &self.g_counter {
+            timers.extend(g_counter.timers().into_iter().map(|t| t.lift(|w: &mut Workloads| w.g_counter.as_mut())));
+        }
+        if let Some(txn) = &self.txn {
+            timers.extend(txn.timers().into_iter().map(|t| t.lift(|w: &mut Workloads| w.txn.as_mut())));
+        }
+        timers
+    }
+}
+
+// Hands a message to one workload in its own payload type, and its reply back as json
+pub(crate) fn forward<H: Handler>(handler: &mut H, rt: &Runtime, msg: &Message<Value>) -> Result<Option<Value>, RpcError> {
+    let msg: Message<H::Payload> = msg
+        .clone()
+        .decode()
+        .map_err(|e| RpcError::new(ErrorCode::MalformedRequest, e.to_string()))?;
+    match handler.handle(rt, &msg)? {
+        Some(reply) => Ok(Some(serde_json::to_value(reply)?)),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,327 @@
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Duration;
+
+use common::{Network, Simulation};
+use maelstrom_broadcast::broadcast::Broadcast;
+use maelstrom_broadcast::config::Config;
+use maelstrom_broadcast::message_set::Storage;
+use maelstrom_broadcast::rng::hash_bytes;
+use serde_json::{json, Value};
+
+// Starts a cluster with each node linked to the next, like a line
+async fn cluster(n: usize, network: Network) -> Simulation<Broadcast> {
+    cluster_with(n, network, Config::default()).await
+}
+
+async fn cluster_with(n: usize, network: Network, config: Config) -> Simulation<Broadcast> {
+    let mut sim = Simulation::new(n, network, |_| Broadcast::new(config.clone()));
+    let ids = sim.ids();
+
+    let mut topology: HashMap<String, Vec<String>> = HashMap::new();
+    for pair in ids.windows(2) {
+        topology.entry(pair[0].clone()).or_default().push(pair[1].clone());
+        topology.entry(pair[1].clone()).or_default().push(pair[0].clone());
+    }
+    for id in &ids {
+        let reply = sim.call(id, json!({"type": "topology", "topology": topology})).await;
+        assert_eq!(reply["type"], "topology_ok");
+    }
+    sim
+}
+
+// Broadcasts each value to a node chosen round-robin
+async fn broadcast_all(sim: &mut Simulation<Broadcast>, values: impl IntoIterator<Item = u64>) {
+    let ids = sim.ids();
+    for (i, v) in values.into_iter().enumerate() {
+        let reply = sim.call(&ids[i % ids.len()], json!({"type": "broadcast", "message": v})).await;
+        assert_eq!(reply["type"], "broadcast_ok");
+    }
+}
+
+// The digest a peer holding the given values would send for the given regions, or for all of
+// them with none given: a count and xor-hash per non-empty region one level down
+fn digest_of(values: impl IntoIterator<Item = u64>, within: &[Value]) -> Vec<Value> {
+    let region = |h: u64, depth: u64| json!({"depth": depth, "prefix": h & ((1u64 << (6 * depth)) - 1)});
+    let mut buckets: BTreeMap<String, (Value, u64, u64)> = BTreeMap::new();
+    for v in values {
+        let h = hash_bytes(v.to_string().as_bytes());
+        let depth = match within {
+            [] => 1,
+            _ => match within.iter().find(|r| region(h, r["depth"].as_u64().unwrap()) == **r) {
+                Some(r) => r["depth"].as_u64().unwrap() + 1,
+                None => continue,
+            },
+        };
+        let r = region(h, depth);
+        let bucket = buckets.entry(r.to_string()).or_insert((r, 0, 0));
+        bucket.1 += 1;
+        bucket.2 ^= h;
+    }
+    buckets
+        .into_values()
+        .map(|(r, count, hash)| json!({"depth": r["depth"], "prefix": r["prefix"], "count": count, "hash": hash}))
+        .collect()
+}
+
+// Reads every node's view of the message set
+async fn read_all(sim: &mut Simulation<Broadcast>) -> Vec<BTreeSet<u64>> {
+    let mut reads = Vec::new();
+    for id in sim.ids() {
+        let reply = sim.call(&id, json!({"type": "read"})).await;
+        assert_eq!(reply["type"], "read_ok");
+        reads.push(serde_json::from_value(reply["messages"].clone()).unwrap());
+    }
+    reads
+}
+
+#[tokio::test(start_paused = true)]
+async fn every_value_reaches_every_node() {
+    let mut sim = cluster(5, Network::default()).await;
+    broadcast_all(&mut sim, 0..20).await;
+    sim.run_for(Duration::from_secs(2)).await;
+
+    let expected: BTreeSet<u64> = (0..20).collect();
+    for read in read_all(&mut sim).await {
+        assert_eq!(read, expected);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn converges_despite_drops_and_partitions() {
+    let network = Network {
+        seed: 7,
+        min_latency: Duration::from_millis(5),
+        max_latency: Duration::from_millis(50),
+        drop_rate: 0.25,
+    };
+    let mut sim = cluster(6, network).await;
+
+    sim.partition(&[&["n0", "n1", "n2"], &["n3", "n4", "n5"]]);
+    broadcast_all(&mut sim, 0..30).await;
+    sim.run_for(Duration::from_secs(3)).await;
+
+    sim.heal();
+    broadcast_all(&mut sim, 30..40).await;
+    sim.run_for(Duration::from_secs(20)).await;
+
+    let expected: BTreeSet<u64> = (0..40).collect();
+    for read in read_all(&mut sim).await {
+        assert_eq!(read, expected);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn same_seed_gives_same_run() {
+    let mut stats = Vec::new();
+    for _ in 0..2 {
+        let network = Network {
+            drop_rate: 0.1,
+            ..Network::default()
+        };
+        let mut sim = cluster(4, network).await;
+        broadcast_all(&mut sim, 0..10).await;
+        sim.run_for(Duration::from_secs(2)).await;
+        stats.push((sim.sent, sim.dropped));
+    }
+    assert_eq!(stats[0], stats[1]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn incremental_reads_return_only_new_values() {
+    let mut sim = cluster(2, Network::default()).await;
+    broadcast_all(&mut sim, [1, 2]).await;
+    sim.run_for(Duration::from_secs(1)).await;
+
+    let first = sim.call("n1", json!({"type": "read", "since": 0})).await;
+    let mut seen: Vec<u64> = serde_json::from_value(first["messages"].clone()).unwrap();
+    seen.sort();
+    assert_eq!(seen, vec![1, 2]);
+    assert_eq!(first["version"], 2);
+
+    broadcast_all(&mut sim, [3]).await;
+    sim.run_for(Duration::from_secs(1)).await;
+    let second = sim.call("n1", json!({"type": "read", "since": first["version"]})).await;
+    assert_eq!(second["messages"], json!([3]));
+    assert_eq!(second["version"], 3);
+
+    // A plain read still returns everything, with no cursor
+    let plain = sim.call("n1", json!({"type": "read"})).await;
+    assert_eq!(plain["messages"].as_array().unwrap().len(), 3);
+    assert!(plain.get("version").is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn anti_entropy_sends_only_the_difference() {
+    let config = Config {
+        retry_max_attempts: 1,
+        ..Config::default()
+    };
+    let mut sim = cluster_with(2, Network::default(), config).await;
+    broadcast_all(&mut sim, 0..2000).await;
+    sim.run_for(Duration::from_secs(2)).await;
+
+    // Playing a peer that lacks one value, each sync_ok narrows in on it without listing
+    // the rest of the set, and carries no values
+    let held: Vec<u64> = (0..2000).filter(|v| *v != 1234).collect();
+    let mut within: Vec<Value> = Vec::new();
+    let mut rounds = 0;
+    let hashes = loop {
+        let reply = sim.call("n0", json!({"type": "sync", "within": within, "digest": digest_of(held.iter().copied(), &within)})).await;
+        assert_eq!(reply["type"], "sync_ok");
+        assert!(reply.get("messages").is_none());
+        assert!(reply.to_string().len() < 400, "sync_ok took {} bytes", reply.to_string().len());
+
+        rounds += 1;
+        assert!(rounds <= 3, "still narrowing after {} rounds", rounds);
+        match reply.get("deeper") {
+            Some(deeper) => within = deeper.as_array().unwrap().clone(),
+            None => break reply["hashes"].clone(),
+        }
+    };
+    assert!(hashes.as_array().unwrap().contains(&json!(hash_bytes(b"1234"))));
+
+    // The one gossip for 2000 is lost, and never retried
+    sim.partition(&[&["n0"], &["n1"]]);
+    broadcast_all(&mut sim, [2000]).await;
+    sim.run_for(Duration::from_secs(1)).await;
+    let before = sim.call("n0", json!({"type": "stats"})).await["stats"]["gossip_values"].as_u64().unwrap();
+
+    sim.heal();
+    sim.run_for(Duration::from_secs(3)).await;
+    let expected: BTreeSet<u64> = (0..=2000).collect();
+    for read in read_all(&mut sim).await {
+        assert_eq!(read, expected);
+    }
+
+    // Repair cost a value or two, not the buckets it fell in
+    let after = sim.call("n0", json!({"type": "stats"})).await["stats"]["gossip_values"].as_u64().unwrap();
+    assert!(after - before <= 2, "repair sent {} values", after - before);
+}
+
+#[tokio::test(start_paused = true)]
+async fn restarted_node_recovers_its_messages() {
+    let dir = std::env::temp_dir().join(format!("broadcast-restart-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = Config {
+        data_dir: Some(dir.clone()),
+        ..Config::default()
+    };
+
+    let mut sim = Simulation::new(1, Network::default(), |_| Broadcast::new(config.clone()));
+    broadcast_all(&mut sim, [1, 2, 3]).await;
+    drop(sim);
+
+    // A fresh process pointed at the same directory picks up where the old one left off
+    let mut sim = Simulation::new(1, Network::default(), |_| Broadcast::new(config.clone()));
+    broadcast_all(&mut sim, [3, 4]).await;
+    assert_eq!(read_all(&mut sim).await, vec![(1..=4).collect::<BTreeSet<u64>>()]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn repeated_init_leaves_recovered_state_alone() {
+    let dir = std::env::temp_dir().join(format!("broadcast-reinit-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = Config {
+        data_dir: Some(dir.clone()),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(1, Network::default(), |_| Broadcast::new(config.clone()));
+    broadcast_all(&mut sim, 0..20).await;
+
+    let reply = sim.call("n0", json!({"type": "init", "node_id": "n0", "node_ids": ["n0"]})).await;
+    assert_eq!(reply["type"], "init_ok");
+
+    // A digest of exactly what we broadcast still matches the node's own
+    let reply = sim.call("n0", json!({"type": "sync", "digest": digest_of(0..20, &[])})).await;
+    assert_eq!(reply["buckets"], json!([]));
+    assert!(reply.get("deeper").is_none());
+    assert_eq!(read_all(&mut sim).await, vec![(0..20).collect::<BTreeSet<u64>>()]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn stats_count_traffic_and_duplicates() {
+    let mut sim = cluster(3, Network::default()).await;
+    broadcast_all(&mut sim, 0..3).await;
+    sim.run_for(Duration::from_secs(2)).await;
+
+    // n1 has already heard about 0 from its neighbors
+    let reply = sim.call("n1", json!({"type": "broadcast", "message": 0})).await;
+    assert_eq!(reply["type"], "broadcast_ok");
+
+    let reply = sim.call("n1", json!({"type": "stats"})).await;
+    assert_eq!(reply["type"], "stats_ok");
+    let stats = &reply["stats"];
+    assert_eq!(stats["traffic"]["received"]["broadcast"], 2);
+    assert_eq!(stats["traffic"]["received"]["stats"], 1);
+    assert!(stats["traffic"]["sent"]["gossip"].as_u64().unwrap() > 0);
+    assert!(stats["traffic"]["bytes_sent"].as_u64().unwrap() > 0);
+    assert!(stats["duplicates"].as_u64().unwrap() >= 1);
+    assert!(stats["acked"].as_u64().unwrap() > 0);
+    assert_eq!(stats["retries"], 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn any_json_value_can_be_broadcast() {
+    let mut sim = cluster(3, Network::default()).await;
+    let values = [json!(0), json!("zero"), json!(null), json!([1, 2]), json!({"a": 1, "b": 2})];
+    for v in &values {
+        let reply = sim.call("n0", json!({"type": "broadcast", "message": v})).await;
+        assert_eq!(reply["type"], "broadcast_ok");
+    }
+
+    // The same object with its keys the other way round is the same value
+    let reply = sim.call("n2", json!({"type": "broadcast", "message": {"b": 2, "a": 1}})).await;
+    assert_eq!(reply["type"], "broadcast_ok");
+    sim.run_for(Duration::from_secs(2)).await;
+
+    for id in sim.ids() {
+        let reply = sim.call(&id, json!({"type": "read"})).await;
+        let mut read: Vec<String> = reply["messages"].as_array().unwrap().iter().map(|v| v.to_string()).collect();
+        let mut expected: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        read.sort();
+        expected.sort();
+        assert_eq!(read, expected, "{} disagrees", id);
+    }
+
+    // A broadcast has to say what it's broadcasting
+    let reply = sim.call("n0", json!({"type": "broadcast"})).await;
+    assert_eq!(reply["type"], "error");
+}
+
+#[tokio::test(start_paused = true)]
+async fn interval_storage_converges_on_integers_only() {
+    let network = Network {
+        seed: 3,
+        drop_rate: 0.2,
+        ..Network::default()
+    };
+    let config = Config {
+        storage: Storage::Intervals,
+        ..Config::default()
+    };
+    let mut sim = cluster_with(4, network, config).await;
+    broadcast_all(&mut sim, (0..50).rev()).await;
+    sim.run_for(Duration::from_secs(10)).await;
+
+    let expected: BTreeSet<u64> = (0..50).collect();
+    for read in read_all(&mut sim).await {
+        assert_eq!(read, expected);
+    }
+
+    // Incremental reads still follow the order values arrived in
+    let first = sim.call("n0", json!({"type": "read", "since": 0})).await;
+    assert_eq!(first["version"], 50);
+    broadcast_all(&mut sim, [50]).await;
+    let second = sim.call("n0", json!({"type": "read", "since": 50})).await;
+    assert_eq!(second["messages"], json!([50]));
+
+    let reply = sim.call("n0", json!({"type": "broadcast", "message": "zero"})).await;
+    assert_eq!(reply["type"], "error");
+    assert_eq!(reply["code"], 12);
+}
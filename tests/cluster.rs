@@ -0,0 +1,41 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use maelstrom_broadcast::cluster::{self, Cluster, ClusterConfig};
+use serde_json::json;
+use tokio::task::JoinSet;
+
+#[tokio::test]
+async fn local_cluster_spreads_broadcasts_over_tcp() {
+    let config = ClusterConfig {
+        nodes: 3,
+        client_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        bin: env!("CARGO_BIN_EXE_maelstrom-broadcast").into(),
+        verbose: false,
+    };
+    let cluster = Cluster::start(config).await.unwrap();
+    let addr = cluster.client_addr();
+
+    let reply = cluster::call(addr, "n0", json!({"type": "broadcast", "message": 5})).await.unwrap();
+    assert_eq!(reply["type"], "broadcast_ok");
+
+    // Gossip goes out on a timer, so give it a moment to get around
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let reply = cluster::call(addr, "n2", json!({"type": "read"})).await.unwrap();
+    assert_eq!(reply["messages"], json!([5]));
+
+    // Calls in flight at once each get their own reply
+    let mut calls = JoinSet::new();
+    for i in 0..8 {
+        calls.spawn(cluster::call(addr, "n1", json!({"type": "broadcast", "message": 10 + i})));
+    }
+    let replies = tokio::time::timeout(Duration::from_secs(5), async {
+        let mut replies = Vec::new();
+        while let Some(reply) = calls.join_next().await {
+            replies.push(reply.unwrap().unwrap());
+        }
+        replies
+    });
+    let replies = replies.await.expect("a call never got its reply");
+    assert!(replies.iter().all(|reply| reply["type"] == "broadcast_ok"), "{:?}", replies);
+}
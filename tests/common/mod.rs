@@ -0,0 +1,228 @@
+// An in-process, deterministic stand-in for Maelstrom: N nodes whose messages travel over a
+// simulated network with seeded latency, drops and partitions, on tokio's paused clock.
+
+#![allow(dead_code)]
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::time::Duration;
+
+use maelstrom_broadcast::maelstrom_node::{Handler, Message, Node};
+use maelstrom_broadcast::rng::Rng;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+// The client every request comes from
+pub const CLIENT: &str = "c1";
+
+// How the simulated network treats node-to-node messages. Client links are always instant
+// and reliable, as they are in Maelstrom.
+#[derive(Clone, Debug)]
+pub struct Network {
+    pub seed: u64,
+    pub min_latency: Duration,
+    pub max_latency: Duration,
+    pub drop_rate: f64,
+}
+
+impl Default for Network {
+    fn default() -> Network {
+        Network {
+            seed: 1,
+            min_latency: Duration::from_millis(1),
+            max_latency: Duration::from_millis(10),
+            drop_rate: 0.0,
+        }
+    }
+}
+
+// A message on the wire: when it lands, a tiebreaker to keep ordering stable, where it's going
+// and the line itself
+type InFlight = Reverse<(Instant, u64, String, String)>;
+
+pub struct Simulation<H: Handler> {
+    nodes: BTreeMap<String, (Node<H>, mpsc::UnboundedReceiver<String>)>,
+    network: Network,
+    rng: Rng,
+    queue: BinaryHeap<InFlight>,
+    seq: u64,
+
+    // Directed links that are currently cut
+    cut: HashSet<(String, String)>,
+
+    // Everything nodes have sent back to the client
+    replies: Vec<Message<Value>>,
+    client_msg_id: u64,
+
+    // Messages put on the wire, and those lost to drops or partitions
+    pub sent: u64,
+    pub dropped: u64,
+}
+
+impl<H: Handler> Simulation<H> {
+    // Starts n nodes named n0, n1, ... and runs the init handshake on each
+    pub fn new(n: usize, network: Network, mut make: impl FnMut(&str) -> H) -> Simulation<H> {
+        let ids: Vec<String> = (0..n).map(|i| format!("n{}", i)).collect();
+        let mut sim = Simulation {
+            nodes: BTreeMap::new(),
+            rng: Rng::new(network.seed),
+            network,
+            queue: BinaryHeap::new(),
+            seq: 0,
+            cut: HashSet::new(),
+            replies: Vec::new(),
+            client_msg_id: 0,
+            sent: 0,
+            dropped: 0,
+        };
+
+        for id in &ids {
+            let (tx, rx) = mpsc::unbounded_channel();
+            sim.nodes.insert(id.clone(), (Node::new(make(id), tx), rx));
+        }
+        for id in &ids {
+            sim.request(id, json!({"type": "init", "node_id": id, "node_ids": ids}));
+        }
+        sim
+    }
+
+    // Names of every node, in order
+    pub fn ids(&self) -> Vec<String> {
+        self.nodes.keys().cloned().collect()
+    }
+
+    // Direct access to a node's handler, for checking state the protocol doesn't expose
+    pub fn handler(&self, id: &str) -> &H {
+        &self.nodes[id].0.handler
+    }
+
+    // Queues a client request to a node and returns its msg_id
+    pub fn request(&mut self, dest: &str, mut body: Value) -> u64 {
+        self.client_msg_id += 1;
+        body["msg_id"] = json!(self.client_msg_id);
+        let line = json!({"src": CLIENT, "dest": dest, "body": body}).to_string();
+        self.enqueue(Instant::now(), dest, line);
+        self.client_msg_id
+    }
+
+    // Sends a request and runs the simulation until its reply arrives, returning the reply body
+    pub async fn call(&mut self, dest: &str, body: Value) -> Value {
+        let msg_id = self.request(dest, body);
+        for _ in 0..1000 {
+            if let Some(reply) = self.reply_to(msg_id) {
+                return reply;
+            }
+            self.run_for(Duration::from_millis(1)).await;
+        }
+        panic!("no reply from {} to msg {}", dest, msg_id);
+    }
+
+    // The reply body to a client request, if it has arrived
+    pub fn reply_to(&self, msg_id: u64) -> Option<Value> {
+        self.replies
+            .iter()
+            .find(|m| m.body.in_reply_to == Some(msg_id))
+            .map(|m| m.body.payload.clone())
+    }
+
+    // Splits the nodes into groups that can only talk among themselves
+    pub fn partition(&mut self, groups: &[&[&str]]) {
+        let group_of = |id: &str| groups.iter().position(|g| g.contains(&id));
+        for a in self.nodes.keys() {
+            for b in self.nodes.keys() {
+                if a != b && (group_of(a).is_none() || group_of(a) != group_of(b)) {
+                    self.cut.insert((a.clone(), b.clone()));
+                }
+            }
+        }
+    }
+
+    // Restores every link
+    pub fn heal(&mut self) {
+        self.cut.clear();
+    }
+
+    // Advances simulated time, delivering messages and firing timers as they come due
+    pub async fn run_for(&mut self, duration: Duration) {
+        let end = Instant::now() + duration;
+        loop {
+            let next_msg = self.queue.peek().map(|Reverse((at, ..))| *at);
+            let next_timer = self.nodes.values().filter_map(|(node, _)| node.next_timer()).min();
+            let Some(next) = next_msg.into_iter().chain(next_timer).min().filter(|t| *t <= end) else {
+                break;
+            };
+            advance_to(next).await;
+            self.route_all();
+
+            while let Some(Reverse((at, ..))) = self.queue.peek() {
+                if *at > Instant::now() {
+                    break;
+                }
+                let Reverse((_, _, dest, line)) = self.queue.pop().unwrap();
+                self.nodes.get_mut(&dest).unwrap().0.receive(&line);
+                self.route(&dest);
+            }
+
+            for id in self.ids() {
+                self.nodes.get_mut(&id).unwrap().0.run_timers();
+                self.route(&id);
+            }
+        }
+        advance_to(end).await;
+        self.route_all();
+    }
+
+    // Handlers may answer from tasks of their own, which only get to run while time moves, so
+    // their output is picked up whenever it does
+    fn route_all(&mut self) {
+        for id in self.ids() {
+            self.route(&id);
+        }
+    }
+
+    // Moves everything a node has sent onto the network
+    fn route(&mut self, src: &str) {
+        let mut lines = Vec::new();
+        while let Ok(line) = self.nodes.get_mut(src).unwrap().1.try_recv() {
+            lines.push(line);
+        }
+
+        for line in lines {
+            let msg: Message<Value> = serde_json::from_str(&line).expect("nodes only send valid messages");
+            if !self.nodes.contains_key(&msg.dest) {
+                self.replies.push(msg);
+                continue;
+            }
+
+            let cut = self.cut.contains(&(src.to_string(), msg.dest.clone()));
+            if cut || self.next_f64() < self.network.drop_rate {
+                self.dropped += 1;
+                continue;
+            }
+
+            let spread = self.network.max_latency.saturating_sub(self.network.min_latency);
+            let latency = self.network.min_latency + spread.mul_f64(self.next_f64());
+            self.enqueue(Instant::now() + latency, &msg.dest, line);
+        }
+    }
+
+    fn enqueue(&mut self, at: Instant, dest: &str, line: String) {
+        self.seq += 1;
+        self.sent += 1;
+        self.queue.push(Reverse((at, self.seq, dest.to_string(), line)));
+    }
+
+    // A uniform value in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Moves tokio's paused clock forward to the given instant
+async fn advance_to(at: Instant) {
+    let now = Instant::now();
+    if at > now {
+        tokio::time::advance(at - now).await;
+    }
+}
@@ -0,0 +1,54 @@
+use std::env;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use maelstrom_broadcast::config::Config;
+
+// The environment is shared by every test in this process, so they take turns with it
+static ENV: Mutex<()> = Mutex::new(());
+
+#[test]
+fn zero_gossip_interval_falls_back_to_the_default() {
+    let _env = ENV.lock().unwrap();
+    env::set_var("GOSSIP_INTERVAL_MS", "0");
+    let config = Config::from_env().unwrap();
+    env::remove_var("GOSSIP_INTERVAL_MS");
+
+    assert_eq!(config.gossip_interval, Config::default().gossip_interval);
+    assert!(!config.gossip_interval.is_zero());
+
+    env::set_var("GOSSIP_INTERVAL_MS", "25");
+    let config = Config::from_env().unwrap();
+    env::remove_var("GOSSIP_INTERVAL_MS");
+    assert_eq!(config.gossip_interval, Duration::from_millis(25));
+}
+
+#[test]
+fn zero_retry_timings_fall_back_to_the_defaults() {
+    let _env = ENV.lock().unwrap();
+    env::set_var("RETRY_TIMEOUT_MS", "0");
+    env::set_var("RETRY_MAX_BACKOFF_MS", "0");
+    let config = Config::from_env().unwrap();
+    env::remove_var("RETRY_TIMEOUT_MS");
+    env::remove_var("RETRY_MAX_BACKOFF_MS");
+
+    let defaults = Config::default();
+    assert_eq!(config.retry_timeout, defaults.retry_timeout);
+    assert_eq!(config.retry_max_backoff, defaults.retry_max_backoff);
+}
+
+#[test]
+fn bad_workloads_stop_startup() {
+    let _env = ENV.lock().unwrap();
+    for list in ["echo,brodcast", "broadcast,txn", ""] {
+        env::set_var("WORKLOADS", list);
+        let result = Config::from_env();
+        env::remove_var("WORKLOADS");
+        assert!(result.unwrap_err().starts_with("WORKLOADS: "), "{:?} was accepted", list);
+    }
+
+    env::set_var("WORKLOADS", "echo,txn");
+    let config = Config::from_env().unwrap();
+    env::remove_var("WORKLOADS");
+    assert!(config.workloads.echo && config.workloads.txn && !config.workloads.broadcast);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{Network, Simulation};
+use maelstrom_broadcast::config::Config;
+use maelstrom_broadcast::workloads::Workloads;
+use serde_json::json;
+
+// Starts a g-counter cluster the way Maelstrom does, with init and nothing else
+fn cluster(n: usize, network: Network) -> Simulation<Workloads> {
+    let config = Config {
+        workloads: "g-counter".parse().unwrap(),
+        ..Config::default()
+    };
+    Simulation::new(n, network, |_| Workloads::new(config.clone()))
+}
+
+#[tokio::test(start_paused = true)]
+async fn adds_everywhere_sum_up_everywhere() {
+    let network = Network {
+        drop_rate: 0.2,
+        ..Network::default()
+    };
+    let mut sim = cluster(3, network);
+
+    sim.partition(&[&["n0"], &["n1", "n2"]]);
+    let ids = sim.ids();
+    for i in 0..30u64 {
+        let reply = sim.call(&ids[i as usize % ids.len()], json!({"type": "add", "delta": i})).await;
+        assert_eq!(reply["type"], "add_ok");
+    }
+    sim.run_for(Duration::from_secs(2)).await;
+
+    // Each side only knows its own adds while cut off
+    let reply = sim.call("n0", json!({"type": "read"})).await;
+    assert_eq!(reply["value"], (0..30u64).step_by(3).sum::<u64>());
+
+    sim.heal();
+    sim.run_for(Duration::from_secs(10)).await;
+    for id in &ids {
+        let reply = sim.call(id, json!({"type": "read"})).await;
+        assert_eq!(reply["type"], "read_ok");
+        assert_eq!(reply["value"], (0..30u64).sum::<u64>(), "{} disagrees", id);
+    }
+
+    // Broadcast isn't part of the counter's protocol, so clients can't smuggle values in with it
+    let reply = sim.call("n0", json!({"type": "broadcast", "message": 5})).await;
+    assert_eq!(reply["code"], 10);
+}
+
+#[tokio::test(start_paused = true)]
+async fn counts_survive_a_restart_in_constant_space() {
+    let dir = std::env::temp_dir().join(format!("g-counter-restart-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = Config {
+        workloads: "g-counter".parse().unwrap(),
+        data_dir: Some(dir.clone()),
+        ..Config::default()
+    };
+
+    // Every add lands in its own flush, which used to mean a new value to keep forever
+    let mut sim = Simulation::new(1, Network::default(), |_| Workloads::new(config.clone()));
+    for _ in 0..200 {
+        let reply = sim.call("n0", json!({"type": "add", "delta": 1})).await;
+        assert_eq!(reply["type"], "add_ok");
+        sim.run_for(Duration::from_millis(100)).await;
+    }
+    sim.run_for(config.snapshot_interval).await;
+    drop(sim);
+
+    let snapshot: Vec<serde_json::Value> = serde_json::from_slice(&std::fs::read(dir.join("n0.snapshot")).unwrap()).unwrap();
+    assert_eq!(snapshot, vec![json!({"node": "n0", "count": 200})]);
+
+    let mut sim = Simulation::new(1, Network::default(), |_| Workloads::new(config.clone()));
+    let reply = sim.call("n0", json!({"type": "read"})).await;
+    assert_eq!(reply["value"], 200);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
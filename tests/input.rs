@@ -0,0 +1,69 @@
+use maelstrom_broadcast::echo::Echo;
+use maelstrom_broadcast::maelstrom_node::{Inbound, LineTransport, Node, Runtime, Transport};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+// Feeds one line to a fresh echo node and returns whatever it sent back
+fn replies_to(line: &str) -> Vec<Value> {
+    let (tx, mut out) = mpsc::unbounded_channel();
+    let mut node = Node::new(Echo, tx);
+    node.receive(line);
+
+    let mut replies = Vec::new();
+    while let Ok(line) = out.try_recv() {
+        replies.push(serde_json::from_str(&line).unwrap());
+    }
+    replies
+}
+
+#[test]
+fn unreadable_lines_are_skipped() {
+    for line in ["", "   ", "not json at all", "{\"dest\": \"n0\", \"body\": ", "[1, 2, 3]"] {
+        assert!(replies_to(line).is_empty(), "{:?} got a reply", line);
+    }
+}
+
+#[test]
+fn truncated_line_is_answered_when_the_sender_survives() {
+    let replies = replies_to(r#"{"src": "c1", "dest": "n0", "body": {"type": "echo", "msg_id": 4, "echo": "hel"#);
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0]["dest"], "c1");
+    assert_eq!(replies[0]["body"]["type"], "error");
+    assert_eq!(replies[0]["body"]["code"], 12);
+    assert_eq!(replies[0]["body"]["in_reply_to"], 4);
+
+    // A msg_id cut off mid-number can't be trusted, and without one there's nobody to answer
+    assert!(replies_to(r#"{"src": "c1", "dest": "n0", "body": {"type": "echo", "msg_id": 4"#).is_empty());
+}
+
+#[test]
+fn valid_json_with_a_bad_body_is_answered() {
+    let replies = replies_to(r#"{"src": "c1", "dest": "n0", "body": {"type": "echo", "msg_id": 5}}"#);
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0]["body"]["code"], 12);
+    assert_eq!(replies[0]["body"]["in_reply_to"], 5);
+}
+
+#[tokio::test]
+async fn node_keeps_serving_after_bad_input() {
+    let (mut client, server) = tokio::io::duplex(4096);
+    let (reader, writer) = tokio::io::split(server);
+    let running = tokio::spawn(Runtime::run_with(Echo, LineTransport::new(reader, writer)));
+
+    client.write_all(b"\xff\xfe\x00garbage\n\n").await.unwrap();
+    client.write_all(b"{\"src\": \"c1\", \"dest\": \"n0\", \"body\": {\"type\": \"echo\", \"msg_id\": 1, \"echo\": \"\xff\"}}\n").await.unwrap();
+    client.write_all(b"{\"src\": \"c1\", \"dest\": \"n0\", \"body\": {\"type\": \"echo\", \"msg_id\": 2, \"echo\": \"hi\"}}\n").await.unwrap();
+
+    let (client_reader, client_writer) = tokio::io::split(client);
+    let (mut inbound, outbound) = LineTransport::new(client_reader, client_writer).split();
+    let reply: Value = serde_json::from_str(&inbound.recv().await.unwrap().unwrap()).unwrap();
+    assert_eq!((&reply["body"]["echo"], &reply["body"]["in_reply_to"]), (&json!("\u{fffd}"), &json!(1)));
+    let reply: Value = serde_json::from_str(&inbound.recv().await.unwrap().unwrap()).unwrap();
+    assert_eq!((&reply["body"]["echo"], &reply["body"]["in_reply_to"]), (&json!("hi"), &json!(2)));
+
+    // Hanging up is a clean shutdown
+    drop(outbound);
+    drop(inbound);
+    running.await.unwrap().unwrap();
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{Network, Simulation};
+use maelstrom_broadcast::config::Config;
+use maelstrom_broadcast::rng::hash_bytes;
+use maelstrom_broadcast::workloads::Workloads;
+use serde_json::json;
+
+#[tokio::test(start_paused = true)]
+async fn logs_are_shared_by_every_node() {
+    let config = Config {
+        workloads: "kafka".parse().unwrap(),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(3, Network::default(), |_| Workloads::new(config.clone()));
+    let ids = sim.ids();
+
+    // Append to a handful of keys through every node; offsets count up per key
+    let keys = ["a", "b", "c", "d", "e"];
+    for round in 0..3u64 {
+        for (i, key) in keys.iter().enumerate() {
+            let node = &ids[(i + round as usize) % ids.len()];
+            let reply = sim.call(node, json!({"type": "send", "key": key, "msg": round * 10 + i as u64})).await;
+            assert_eq!(reply["type"], "send_ok");
+            assert_eq!(reply["offset"], round);
+        }
+    }
+
+    // Any node can poll any key
+    let offsets = json!({"a": 0, "c": 1, "e": 2, "missing": 0});
+    for id in &ids {
+        let reply = sim.call(id, json!({"type": "poll", "offsets": offsets})).await;
+        assert_eq!(reply["type"], "poll_ok");
+        assert_eq!(reply["msgs"], json!({"a": [[0, 0], [1, 10], [2, 20]], "c": [[1, 12], [2, 22]], "e": [[2, 24]]}));
+    }
+
+    // Commits made through one node are visible through the others, and never go backwards
+    let reply = sim.call("n0", json!({"type": "commit_offsets", "offsets": {"a": 2, "b": 1, "c": 1}})).await;
+    assert_eq!(reply["type"], "commit_offsets_ok");
+    let reply = sim.call("n1", json!({"type": "commit_offsets", "offsets": {"a": 1}})).await;
+    assert_eq!(reply["type"], "commit_offsets_ok");
+
+    let reply = sim.call("n2", json!({"type": "list_committed_offsets", "keys": ["a", "b", "c", "d"]})).await;
+    assert_eq!(reply["type"], "list_committed_offsets_ok");
+    assert_eq!(reply["offsets"], json!({"a": 2, "b": 1, "c": 1}));
+}
+
+#[tokio::test(start_paused = true)]
+async fn unreachable_owners_fail_together_within_one_timeout() {
+    let config = Config {
+        workloads: "kafka".parse().unwrap(),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(3, Network::default(), |_| Workloads::new(config.clone()));
+
+    // Keys are owned by whichever node their hash lands on
+    let owned_by = |n: u64| (0..).map(|i| format!("k{}", i)).find(|k| hash_bytes(k.as_bytes()) % 3 == n).unwrap();
+    let keys = [owned_by(1), owned_by(2)];
+
+    // With both owners cut off, the client hears back once the first forward times out
+    sim.partition(&[&["n0"], &["n1", "n2"]]);
+    let id = sim.request("n0", json!({"type": "poll", "offsets": {&keys[0]: 0, &keys[1]: 0}}));
+    sim.run_for(Duration::from_millis(1100)).await;
+
+    // The forwarding task only gets to answer once time moves again
+    sim.run_for(Duration::from_millis(10)).await;
+    let reply = sim.reply_to(id).expect("no reply within a single rpc timeout");
+    assert_eq!(reply["type"], "error");
+    assert_eq!(reply["code"], 0);
+}
@@ -0,0 +1,50 @@
+use maelstrom_broadcast::echo::Echo;
+use maelstrom_broadcast::maelstrom_node::{Kv, KvError, Runtime};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+// Takes the next request off the wire and answers it the way a KV service would
+async fn answer(rt: &Runtime, out: &mut mpsc::UnboundedReceiver<String>, reply: Value) -> Value {
+    let request: Value = serde_json::from_str(&out.recv().await.unwrap()).unwrap();
+    let mut body = reply;
+    body["in_reply_to"] = request["body"]["msg_id"].clone();
+    let line = json!({"src": request["dest"], "dest": request["src"], "body": body}).to_string();
+    rt.receive(&mut Echo, &line);
+    request
+}
+
+#[tokio::test]
+async fn kv_requests_and_errors_round_trip() {
+    let (tx, mut out) = mpsc::unbounded_channel();
+    let rt = Runtime::new(tx);
+    let kv = Kv::lin(&rt);
+
+    let read = tokio::spawn({
+        let kv = kv.clone();
+        async move { kv.read::<_, u64>("x").await }
+    });
+    let request = answer(&rt, &mut out, json!({"type": "read_ok", "value": 3})).await;
+    assert_eq!(request["dest"], "lin-kv");
+    assert_eq!(request["body"]["key"], "x");
+    assert_eq!(read.await.unwrap(), Ok(3));
+
+    let cas = tokio::spawn({
+        let kv = kv.clone();
+        async move { kv.cas("x", 4, 5, true).await }
+    });
+    let request = answer(&rt, &mut out, json!({"type": "error", "code": 22, "text": "expected 4, had 3"})).await;
+    assert_eq!(request["body"]["create_if_not_exists"], true);
+    assert_eq!(cas.await.unwrap(), Err(KvError::PreconditionFailed("expected 4, had 3".to_string())));
+
+    let missing = tokio::spawn({
+        let kv = kv.clone();
+        async move { kv.read::<_, u64>("y").await }
+    });
+    answer(&rt, &mut out, json!({"type": "error", "code": 20, "text": "not found"})).await;
+    assert_eq!(missing.await.unwrap(), Err(KvError::KeyDoesNotExist("not found".to_string())));
+
+    let write = tokio::spawn(async move { kv.write("y", json!([1, 2])).await });
+    let request = answer(&rt, &mut out, json!({"type": "write_ok"})).await;
+    assert_eq!(request["body"]["value"], json!([1, 2]));
+    assert_eq!(write.await.unwrap(), Ok(()));
+}
@@ -0,0 +1,137 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use maelstrom_broadcast::message_set::{MessageSet, Storage, RECENT_ARRIVALS};
+use maelstrom_broadcast::rng::Rng;
+use serde_json::json;
+
+// Counts the bytes each thread has live on the heap, so a test can weigh what it builds
+// without other tests running alongside getting in the way
+struct Counting;
+
+thread_local! {
+    static LIVE: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE.with(|live| live.set(live.get() + layout.size() as isize));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.with(|live| live.set(live.get() - layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+// Bytes left on the heap by building a set from the given values
+fn weigh(storage: Storage, values: &[u64]) -> isize {
+    let before = LIVE.with(Cell::get);
+    let mut set = MessageSet::new(storage);
+    for v in values {
+        set.insert(json!(v));
+    }
+    let after = LIVE.with(Cell::get);
+    assert_eq!(set.len(), values.len());
+    after - before
+}
+
+#[test]
+fn intervals_merge_runs_and_skip_duplicates() {
+    let mut set = MessageSet::new(Storage::Intervals);
+    for v in [5, 3, 4, 9, 4, 8, 0] {
+        set.insert(json!(v));
+    }
+    assert!(!set.insert(json!(3)));
+    assert!(!set.insert(json!(9)));
+    assert!(set.insert(json!(7)));
+
+    // Positions follow arrival order, while a full read comes back sorted
+    assert_eq!(set.len(), 7);
+    assert_eq!(set.since(0).into_values(), vec![json!(5), json!(3), json!(4), json!(9), json!(8), json!(0), json!(7)]);
+    assert_eq!(set.since(5).into_values(), vec![json!(0), json!(7)]);
+    assert_eq!(set.all().into_values(), vec![json!(0), json!(3), json!(4), json!(5), json!(7), json!(8), json!(9)]);
+    assert_eq!(serde_json::to_value(set.all()).unwrap(), json!([0, 3, 4, 5, 7, 8, 9]));
+    match &set {
+        MessageSet::Intervals { runs, .. } => assert_eq!(runs.len(), 3),
+        MessageSet::Json { .. } => unreachable!(),
+    }
+}
+
+#[test]
+fn intervals_refuse_what_they_cannot_hold() {
+    let mut set = MessageSet::new(Storage::Intervals);
+    for v in [json!("1"), json!(-1), json!(1.5), json!(null)] {
+        assert!(set.check(&v).is_err(), "{} was accepted", v);
+        assert!(!set.insert(v));
+    }
+    assert!(set.is_empty());
+    assert!(set.check(&json!(u64::MAX)).is_ok());
+    assert!(set.insert(json!(u64::MAX)));
+}
+
+#[test]
+fn backends_agree_on_hashes() {
+    let mut json_set = MessageSet::new(Storage::Json);
+    let mut intervals = MessageSet::new(Storage::Intervals);
+    for v in [12, 0, 7, u64::MAX, 1000] {
+        json_set.insert(json!(v));
+        intervals.insert(json!(v));
+    }
+    let mut hashes: Vec<u64> = json_set.hashes().collect();
+    hashes.sort();
+    let mut others: Vec<u64> = intervals.hashes().collect();
+    others.sort();
+    assert_eq!(hashes, others);
+    for h in hashes {
+        assert_eq!(json_set.matching(|x| x == h), intervals.matching(|x| x == h));
+    }
+}
+
+#[test]
+fn storage_parses_from_its_names() {
+    assert_eq!("json".parse(), Ok(Storage::Json));
+    assert_eq!("intervals".parse(), Ok(Storage::Intervals));
+    assert_eq!("runs".parse(), Ok(Storage::Intervals));
+    assert!("sqlite".parse::<Storage>().is_err());
+}
+
+#[test]
+fn intervals_list_everything_for_reads_from_before_their_recent_arrivals() {
+    let mut set = MessageSet::new(Storage::Intervals);
+    let n = RECENT_ARRIVALS as u64 + 10;
+    for v in 0..n {
+        set.insert(json!(v));
+    }
+    assert_eq!(set.since(20).into_values(), (20..n).map(|v| json!(v)).collect::<Vec<_>>());
+    assert_eq!(set.since(5), set.all());
+    assert_eq!(set.since(5).len(), n as usize);
+}
+
+#[test]
+fn intervals_stay_flat_as_a_dense_range_grows() {
+    let mut values: Vec<u64> = (0..200_000).collect();
+    Rng::new(9).shuffle(&mut values[..20_000]);
+    Rng::new(9).shuffle(&mut values[20_000..]);
+
+    // Ten times the values, in one run either way, take no more room
+    let small = weigh(Storage::Intervals, &values[..20_000]);
+    let large = weigh(Storage::Intervals, &values);
+    assert!(large <= small, "{} values took {} bytes against {} for {}", values.len(), large, small, 20_000);
+}
+
+#[test]
+fn intervals_weigh_a_fraction_of_json() {
+    // A dense range arriving out of order, as it does from gossip
+    let mut values: Vec<u64> = (0..50_000).collect();
+    Rng::new(9).shuffle(&mut values);
+
+    let json = weigh(Storage::Json, &values);
+    let intervals = weigh(Storage::Intervals, &values);
+    assert!(intervals * 6 < json, "intervals took {} bytes against {} for json", intervals, json);
+    assert!(intervals < 16 * values.len() as isize, "intervals took {} bytes", intervals);
+}
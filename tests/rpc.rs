@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use maelstrom_broadcast::broadcast::Payload;
+use maelstrom_broadcast::maelstrom_node::{ErrorCode, Handler, Message, RpcError, Runtime, DEFAULT_RPC_TIMEOUT};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+// Remembers the type of every message that reaches it
+#[derive(Default)]
+struct Recorder {
+    seen: Vec<String>,
+}
+
+impl Handler for Recorder {
+    type Payload = Value;
+
+    fn handle(&mut self, _rt: &Runtime, msg: &Message<Value>) -> Result<Option<Value>, RpcError> {
+        self.seen.push(msg.body.payload["type"].as_str().unwrap_or_default().to_string());
+        Ok(None)
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn timeout_runs_from_when_the_request_is_sent() {
+    let (tx, _out) = mpsc::unbounded_channel();
+    let rt = Runtime::new(tx);
+
+    let start = Instant::now();
+    let call = rt.rpc::<_, Value>("n1", json!({"type": "ping"}));
+    tokio::time::advance(Duration::from_millis(700)).await;
+
+    let e = call.await.unwrap_err();
+    assert_eq!(e.code, ErrorCode::Timeout);
+    assert_eq!(start.elapsed(), DEFAULT_RPC_TIMEOUT);
+}
+
+#[tokio::test(start_paused = true)]
+async fn dropped_call_forgets_its_callback() {
+    let (tx, mut out) = mpsc::unbounded_channel();
+    let rt = Runtime::new(tx);
+
+    let call = rt.rpc::<_, Value>("n1", json!({"type": "ping"}));
+    let request: Value = serde_json::from_str(&out.recv().await.unwrap()).unwrap();
+    drop(call);
+
+    // With nobody waiting on it, a late reply goes to the handler like any other message
+    let mut recorder = Recorder::default();
+    let reply = json!({"src": "n1", "dest": "", "body": {"type": "pong", "in_reply_to": request["body"]["msg_id"]}});
+    rt.receive(&mut recorder, &reply.to_string());
+    assert_eq!(recorder.seen, vec!["pong"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn sent_messages_are_counted_by_type() {
+    let (tx, mut out) = mpsc::unbounded_channel();
+    let rt = Runtime::new(tx);
+
+    rt.send("n1", json!({"messages": [1], "type": "gossip"})).unwrap();
+    rt.send("n1", Payload::Gossip { messages: vec![json!({"type": "not this one"})] }).unwrap();
+    rt.send("n1", Payload::TopologyOk).unwrap();
+    rt.send("n1", json!({"echo": {"type": "echo"}})).unwrap();
+
+    let mut bytes = 0;
+    while let Ok(line) = out.try_recv() {
+        bytes += line.len() as u64 + 1;
+    }
+    let traffic = rt.traffic();
+    assert_eq!(traffic.sent, BTreeMap::from([("".to_string(), 1), ("gossip".to_string(), 2), ("topology_ok".to_string(), 1)]));
+    assert_eq!(traffic.bytes_sent, bytes);
+}
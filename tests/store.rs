@@ -0,0 +1,52 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use maelstrom_broadcast::store::Store;
+use serde_json::json;
+
+// A scratch directory unique to this test and process
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("store-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn reopening_replays_snapshot_then_log() {
+    let dir = scratch("replay");
+    let (mut store, values) = Store::open(&dir, "n0").unwrap();
+    assert!(values.is_empty());
+
+    store.append(&[json!(1), json!(2)]).unwrap();
+    store.compact(&[json!(1), json!(2)]).unwrap();
+    store.append(&[json!(3)]).unwrap();
+    drop(store);
+
+    let (_, values) = Store::open(&dir, "n0").unwrap();
+    assert_eq!(values, vec![json!(1), json!(2), json!(3)]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn torn_last_line_is_ignored() {
+    let dir = scratch("torn");
+    let (mut store, _) = Store::open(&dir, "n0").unwrap();
+    store.append(&[json!(7), json!(8)]).unwrap();
+    drop(store);
+
+    // Simulate a crash halfway through writing the next value
+    let mut log = OpenOptions::new().append(true).open(dir.join("n0.log")).unwrap();
+    log.write_all(br#"{"half": "#).unwrap();
+    drop(log);
+
+    let (mut store, values) = Store::open(&dir, "n0").unwrap();
+    assert_eq!(values, vec![json!(7), json!(8)]);
+
+    // and appending afterwards doesn't get lost behind it
+    store.append(&[json!(9)]).unwrap();
+    drop(store);
+    let (_, values) = Store::open(&dir, "n0").unwrap();
+    assert_eq!(values, vec![json!(7), json!(8), json!(9)]);
+    fs::remove_dir_all(&dir).unwrap();
+}
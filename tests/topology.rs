@@ -0,0 +1,95 @@
+use std::collections::{BTreeSet, HashMap};
+
+use maelstrom_broadcast::topology::Topology;
+
+// Every layout a node can compute for itself, across the arguments worth worrying about
+fn layouts() -> Vec<Topology> {
+    let mut layouts = vec![Topology::Spanning, Topology::Star];
+    for k in 1..=4 {
+        layouts.push(Topology::Tree(k));
+        layouts.push(Topology::Random(k));
+    }
+    for chords in 0..=3 {
+        layouts.push(Topology::RingChords(chords));
+    }
+    layouts
+}
+
+// A ring with a few extra edges, standing in for Maelstrom's suggested grid
+fn suggested(ids: &[String]) -> HashMap<String, Vec<String>> {
+    let n = ids.len();
+    let mut graph: HashMap<String, Vec<String>> = HashMap::new();
+    for i in 0..n {
+        for j in [(i + 1) % n, (i + 3) % n] {
+            if i != j {
+                graph.entry(ids[i].clone()).or_default().push(ids[j].clone());
+                graph.entry(ids[j].clone()).or_default().push(ids[i].clone());
+            }
+        }
+    }
+    graph
+}
+
+#[test]
+fn every_layout_is_symmetric_and_connected() {
+    for n in 1..=30 {
+        let ids: Vec<String> = (0..n).map(|i| format!("n{}", i)).collect();
+        let suggested = suggested(&ids);
+
+        for layout in layouts() {
+            let graph: HashMap<&str, BTreeSet<String>> = ids
+                .iter()
+                .map(|id| {
+                    let neighbors = layout.neighbors(id, &ids, &suggested).unwrap();
+                    (id.as_str(), neighbors.into_iter().collect())
+                })
+                .collect();
+
+            for (id, neighbors) in &graph {
+                assert!(!neighbors.contains(*id), "{:?} links {} to itself with n={}", layout, id, n);
+                for other in neighbors {
+                    assert!(graph[other.as_str()].contains(*id), "{:?} links {} to {} but not back with n={}", layout, id, other, n);
+                }
+            }
+
+            let mut reached = BTreeSet::from([ids[0].clone()]);
+            let mut frontier = vec![ids[0].clone()];
+            while let Some(id) = frontier.pop() {
+                for next in &graph[id.as_str()] {
+                    if reached.insert(next.clone()) {
+                        frontier.push(next.clone());
+                    }
+                }
+            }
+            assert_eq!(reached.len(), n, "{:?} leaves nodes unreachable with n={}", layout, n);
+        }
+    }
+}
+
+#[test]
+fn spanning_tree_keeps_only_tree_edges() {
+    let ids: Vec<String> = (0..12).map(|i| format!("n{}", i)).collect();
+    let suggested = suggested(&ids);
+    let edges: usize = ids
+        .iter()
+        .map(|id| Topology::Spanning.neighbors(id, &ids, &suggested).unwrap().len())
+        .sum();
+    assert_eq!(edges / 2, ids.len() - 1);
+}
+
+#[test]
+fn layouts_parse_from_their_names() {
+    assert_eq!("tree:4".parse(), Ok(Topology::Tree(4)));
+    assert_eq!("random".parse(), Ok(Topology::Random(3)));
+    assert_eq!("ring-chords:0".parse(), Ok(Topology::RingChords(0)));
+    assert!("mesh".parse::<Topology>().is_err());
+}
+
+#[test]
+fn without_a_suggestion_maelstrom_means_everyone() {
+    let ids: Vec<String> = (0..5).map(|i| format!("n{}", i)).collect();
+    assert_eq!(Topology::Maelstrom.default_neighbors("n2", &ids), vec!["n0", "n1", "n3", "n4"]);
+    assert_eq!(Topology::Spanning.default_neighbors("n2", &ids), vec!["n0"]);
+    assert_eq!(Topology::Star.default_neighbors("n0", &ids), vec!["n1", "n2", "n3", "n4"]);
+    assert!(Topology::Maelstrom.default_neighbors("n9", &ids).is_empty());
+}
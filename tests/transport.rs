@@ -0,0 +1,55 @@
+use maelstrom_broadcast::broadcast::Broadcast;
+use maelstrom_broadcast::config::Config;
+use maelstrom_broadcast::maelstrom_node::{channel, ChannelTransport, Inbound, LineTransport, Outbound, Runtime, Transport};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+
+// Sends a client request and waits for the reply body
+async fn call(client: &mut ChannelTransport, msg_id: u64, body: Value) -> Value {
+    let mut body = body;
+    body["msg_id"] = json!(msg_id);
+    client.send(json!({"src": "c1", "dest": "n0", "body": body}).to_string()).unwrap();
+
+    let line = client.recv().await.expect("node hung up");
+    let reply: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(reply["body"]["in_reply_to"], msg_id);
+    reply["body"].clone()
+}
+
+#[tokio::test]
+async fn runs_over_an_in_memory_channel() {
+    let (node, mut client) = channel();
+    let running = tokio::spawn(Runtime::run_with(Broadcast::new(Config::default()), node));
+
+    let init = call(&mut client, 1, json!({"type": "init", "node_id": "n0", "node_ids": ["n0"]})).await;
+    assert_eq!(init["type"], "init_ok");
+
+    call(&mut client, 2, json!({"type": "broadcast", "message": 42})).await;
+    let read = call(&mut client, 3, json!({"type": "read"})).await;
+    assert_eq!(read["messages"], json!([42]));
+
+    // Hanging up is the node's cue to shut down cleanly
+    drop(client);
+    running.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn runs_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let running = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        Runtime::run_with(Broadcast::new(Config::default()), LineTransport::tcp(stream)).await
+    });
+
+    let (mut inbound, mut outbound) = LineTransport::tcp(TcpStream::connect(addr).await.unwrap()).split();
+    let init = json!({"src": "c1", "dest": "n0", "body": {"type": "init", "msg_id": 1, "node_id": "n0", "node_ids": ["n0"]}});
+    outbound.send(init.to_string()).await.unwrap();
+
+    let reply: Value = serde_json::from_str(&inbound.recv().await.unwrap().unwrap()).unwrap();
+    assert_eq!(reply["body"]["type"], "init_ok");
+
+    drop(outbound);
+    drop(inbound);
+    running.await.unwrap().unwrap();
+}
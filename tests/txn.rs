@@ -0,0 +1,63 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{Network, Simulation};
+use maelstrom_broadcast::config::Config;
+use maelstrom_broadcast::message_set::Storage;
+use maelstrom_broadcast::workloads::Workloads;
+use serde_json::json;
+
+#[tokio::test(start_paused = true)]
+async fn replicas_agree_once_writes_have_spread() {
+    let config = Config {
+        workloads: "txn".parse().unwrap(),
+        ..Config::default()
+    };
+    let network = Network {
+        drop_rate: 0.2,
+        ..Network::default()
+    };
+    // Maelstrom never sends txn nodes a topology, so they have to find each other on their own
+    let mut sim = Simulation::new(3, network, |_| Workloads::new(config.clone()));
+    let ids = sim.ids();
+
+    // A transaction sees its own writes straight away
+    let reply = sim.call("n0", json!({"type": "txn", "txn": [["w", 1, 10], ["r", 1, null], ["w", 1, 11], ["r", 2, null]]})).await;
+    assert_eq!(reply["type"], "txn_ok");
+    assert_eq!(reply["txn"], json!([["w", 1, 10], ["r", 1, 10], ["w", 1, 11], ["r", 2, null]]));
+
+    // Concurrent writers everywhere, then everyone settles on the same values
+    for round in 0..5u64 {
+        for (i, id) in ids.iter().enumerate() {
+            let txn = json!([["w", round % 3, round * 10 + i as u64], ["w", 9, i]]);
+            let reply = sim.call(id, json!({"type": "txn", "txn": txn})).await;
+            assert_eq!(reply["type"], "txn_ok");
+        }
+    }
+    sim.run_for(Duration::from_secs(10)).await;
+
+    let read = json!([["r", 0, null], ["r", 1, null], ["r", 2, null], ["r", 9, null]]);
+    let first = sim.call("n0", json!({"type": "txn", "txn": read})).await;
+    for id in &ids {
+        let reply = sim.call(id, json!({"type": "txn", "txn": read})).await;
+        assert_eq!(reply["txn"], first["txn"], "{} disagrees", id);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn integer_storage_setting_still_replicates_writes() {
+    let config = Config {
+        workloads: "txn".parse().unwrap(),
+        storage: Storage::Intervals,
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(2, Network::default(), |_| Workloads::new(config.clone()));
+
+    let reply = sim.call("n0", json!({"type": "txn", "txn": [["w", 1, 5]]})).await;
+    assert_eq!(reply["type"], "txn_ok");
+    sim.run_for(Duration::from_secs(1)).await;
+
+    let reply = sim.call("n1", json!({"type": "txn", "txn": [["r", 1, null]]})).await;
+    assert_eq!(reply["txn"], json!([["r", 1, 5]]));
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use std::collections::HashSet;
+
+use common::{Network, Simulation};
+use maelstrom_broadcast::config::Config;
+use maelstrom_broadcast::workloads::Workloads;
+use serde_json::json;
+
+#[tokio::test(start_paused = true)]
+async fn generated_ids_are_unique_across_nodes() {
+    let mut sim = Simulation::new(3, Network::default(), |_| Workloads::new(Config::default()));
+
+    let mut seen = HashSet::new();
+    for _ in 0..50 {
+        for id in sim.ids() {
+            let reply = sim.call(&id, json!({"type": "generate"})).await;
+            assert_eq!(reply["type"], "generate_ok");
+            assert!(seen.insert(reply["id"].to_string()), "{} was handed out twice", reply["id"]);
+        }
+    }
+
+    // The same node still serves broadcast alongside
+    let reply = sim.call("n0", json!({"type": "broadcast", "message": 1})).await;
+    assert_eq!(reply["type"], "broadcast_ok");
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::{Network, Simulation};
+use maelstrom_broadcast::config::Config;
+use maelstrom_broadcast::workloads::Workloads;
+use serde_json::json;
+
+#[tokio::test(start_paused = true)]
+async fn only_selected_workloads_are_served() {
+    let config = Config {
+        workloads: "echo".parse().unwrap(),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(1, Network::default(), |_| Workloads::new(config.clone()));
+
+    let reply = sim.call("n0", json!({"type": "echo", "echo": {"hello": [1, 2]}})).await;
+    assert_eq!(reply["type"], "echo_ok");
+    assert_eq!(reply["echo"], json!({"hello": [1, 2]}));
+
+    for body in [json!({"type": "generate"}), json!({"type": "read"}), json!({"type": "nonsense"})] {
+        let reply = sim.call("n0", body).await;
+        assert_eq!(reply["type"], "error");
+        assert_eq!(reply["code"], 10);
+    }
+}